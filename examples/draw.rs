@@ -0,0 +1,41 @@
+extern crate minimalist2;
+extern crate image;
+extern crate rand;
+
+use minimalist2::{shape, util};
+use minimalist2::shape::Ellipse;
+use minimalist2::{Alpha, Metric, Drawable, Mutatable};
+
+// Draws two ellipses in the best color for a flat target
+fn main() {
+    let mut rng = rand::thread_rng();
+    let mut img1 = image::ImageBuffer::from_fn(512, 512, |_x, _y| {
+        image::Rgb([0u8,0u8,0u8])
+    });
+    let pixel = img1.get_pixel(512/2, 512/2);
+    println!("source image: {:?}", pixel);
+
+    let img2 = image::ImageBuffer::from_fn(512, 512, |_x, _y| {
+        image::Rgb([100u8,100u8,80u8])
+    });
+    let pixel = img2.get_pixel(512/2, 512/2);
+    println!("target image: {:?}", pixel);
+
+    let mut ellipse1 = Ellipse::new(512/2, 0, 512/2, 512/2, Alpha::Fixed(128));
+    println!("{}", ellipse1);
+    ellipse1.mutate((512, 512), 4.0, &mut rng);
+    println!("{}", ellipse1);
+    let ellipse2 = Ellipse::new(512/2, 511, 512/2, 512/2, Alpha::Fixed(128));
+
+    let lines = shape::Row::full_image(512, 512);
+    let best_color = util::best_color_in_rows(&lines, 128, &img1, &img2, Metric::Rgb, None);
+    println!("best color: {:?}", best_color);
+
+    ellipse1.draw_to_image(&mut img1, best_color, 128);
+    ellipse2.draw_to_image(&mut img1, best_color, 128);
+
+    let pixel = img1.get_pixel(512/2, 512/2);
+    println!("result: {:?}", pixel);
+    img1.save("data/test.png").expect("");
+    img1.save("data/test.jpeg").expect("");
+}
\ No newline at end of file
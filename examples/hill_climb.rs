@@ -0,0 +1,63 @@
+extern crate minimalist2;
+extern crate image;
+extern crate rand;
+
+use image::ImageBuffer;
+
+use minimalist2::{optimize, util};
+use minimalist2::optimize::Evaluator;
+use minimalist2::{ShapeKind, Alpha, Metric};
+
+// Searches for a single shape on the Mona Lisa, printing each stage
+fn main() {
+    hill_climb();
+    best_random_hill_climb();
+}
+
+fn hill_climb() {
+    let mut rng = rand::thread_rng();
+    let kind = ShapeKind::Ellipse;
+    let alpha = Alpha::Fixed(128);
+    let num_rand = 1000;
+    let max_age = 100;
+    let target_img = image::open("data/mona.jpg").expect("opening target").into_rgb8();
+    let size = target_img.dimensions();
+    let background = util::average_image_color(&target_img);
+    let current_img = ImageBuffer::from_fn(size.0, size.1, |_x, _y| {
+        background
+    });
+    let init_error = util::mean_square_error(&current_img, &target_img, None);
+    println!("init_error: {}", init_error);
+    let evaluator = Evaluator::new(&current_img, &target_img, init_error, Metric::Rgb, None);
+    let (shape, error) = optimize::best_random_shape(&kind, alpha, num_rand, 1, &evaluator, &mut rng);
+    println!("{}", shape);
+    println!("{}", error);
+    let (shape, error) = optimize::hill_climb(shape, error, max_age, &evaluator, &mut rng);
+    println!("{}", shape);
+    println!("{}", error);
+}
+
+fn best_random_hill_climb() {
+    let mut rng = rand::thread_rng();
+    let kind = ShapeKind::Ellipse;
+    let alpha = Alpha::Fixed(128);
+    let num_rand = 1000;
+    let max_age = 100;
+    let num_climbs = 4;
+
+    let target_img = image::open("data/mona.jpg").expect("opening target").into_rgb8();
+    let size = target_img.dimensions();
+    let background = util::average_image_color(&target_img);
+    let current_img = ImageBuffer::from_fn(size.0, size.1, |_x, _y| {
+        background
+    });
+
+    let init_error = util::mean_square_error(&current_img, &target_img, None);
+    println!("init_error: {}", init_error);
+
+    let evaluator = Evaluator::new(&current_img, &target_img, init_error, Metric::Rgb, None);
+    let (shape, error) = optimize::best_random_hill_climb(&kind, alpha, num_climbs, max_age, num_rand, num_climbs as usize, &evaluator, &mut rng);
+    println!("{}", shape);
+    println!("{}", error);
+    println!("evaluations: {}", evaluator.evaluations());
+}
@@ -0,0 +1,287 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use image::{RgbImage, Frame, Delay, ColorType};
+use image::buffer::ConvertBuffer;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::png::PngEncoder;
+
+use crate::error;
+
+// Which steps of a model's construction become frames of an animated GIF or
+// APNG.  Only the steps are kept, as the model can replay the image after any
+// step from its shape records when saving.  The first frame is the background
+// before any shape.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    steps: Vec<usize>,
+    // a frame is recorded after every `frame_every` shapes
+    pub frame_every: usize,
+    // milliseconds each frame is shown for
+    pub frame_delay: u32,
+    // extra milliseconds the final frame is shown for before looping
+    pub final_hold: u32,
+}
+
+impl Animation {
+    pub fn new(frame_every: usize) -> Self {
+        Animation {
+            steps: Vec::new(),
+            frame_every: frame_every.max(1),
+            frame_delay: 100,
+            final_hold: 2000,
+        }
+    }
+
+    pub fn delay(mut self, frame_delay: u32) -> Self {
+        self.frame_delay = frame_delay;
+        self
+    }
+
+    pub fn hold(mut self, final_hold: u32) -> Self {
+        self.final_hold = final_hold;
+        self
+    }
+
+    // the steps recorded so far, in increasing order
+    pub fn steps(&self) -> &[usize] {
+        &self.steps
+    }
+
+    // Records the image after `step` shapes if it falls on a frame
+    pub fn record(&mut self, step: usize) {
+        if step % self.frame_every == 0 {
+            self.finish(step);
+        }
+    }
+
+    // Records `step` unless it already has a frame, so the animation always
+    // ends on the finished image
+    pub fn finish(&mut self, step: usize) {
+        if self.steps.last() != Some(&step) {
+            self.steps.push(step);
+        }
+    }
+
+    // milliseconds each of `num_frames` frames is shown for, holding on the
+    // last one
+    fn delays(&self, num_frames: usize) -> impl Iterator<Item = u32> + '_ {
+        let last = num_frames.saturating_sub(1);
+        (0..num_frames).map(move |i| {
+            if i == last { self.frame_delay + self.final_hold } else { self.frame_delay }
+        })
+    }
+
+    // Saves `frames` as a GIF for a .gif path and as an APNG for a .png or
+    // .apng one.  Frames are encoded one at a time as they're made, so a long
+    // animation never holds more than one in memory.
+    pub fn save<P, I>(&self, path: P, frames: I) -> error::Result<()>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = RgbImage>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("gif") => self.save_gif(path, frames),
+            Some("png") | Some("apng") => self.save_apng(path, frames),
+            _ => Err(error::Error::ArgumentError(format!("can't tell the animation format of '{}'", path.display()))),
+        }
+    }
+
+    pub fn save_gif<P, I>(&self, path: P, frames: I) -> error::Result<()>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = RgbImage>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let path = path.as_ref();
+        write_file(path, |file| self.write_gif(file, frames)).map_err(error::at_path(path))
+    }
+
+    pub fn save_apng<P, I>(&self, path: P, frames: I) -> error::Result<()>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = RgbImage>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let path = path.as_ref();
+        write_file(path, |file| self.write_apng(file, frames)).map_err(error::at_path(path))
+    }
+
+    pub fn write_gif<W, I>(&self, w: W, frames: I) -> error::Result<()>
+    where
+        W: Write,
+        I: IntoIterator<Item = RgbImage>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let frames = frames.into_iter();
+        let delays = self.delays(frames.len());
+        // speed 10 quantizes far faster than the default with little loss
+        let mut encoder = GifEncoder::new_with_speed(w, 10);
+        encoder.set_repeat(Repeat::Infinite)?;
+        let frames = frames.zip(delays).map(|(img, delay)| {
+            Frame::from_parts(img.convert(), 0, 0, Delay::from_numer_denom_ms(delay, 1))
+        });
+        encoder.encode_frames(frames)?;
+        Ok(())
+    }
+
+    // Each frame is encoded as a standalone png, whose image data is then
+    // moved into the frame chunks of a single animated png
+    pub fn write_apng<W, I>(&self, mut w: W, frames: I) -> error::Result<()>
+    where
+        W: Write,
+        I: IntoIterator<Item = RgbImage>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let frames = frames.into_iter();
+        let num_frames = frames.len();
+        if num_frames == 0 {
+            return Err(error::Error::ModelError);
+        }
+        let delays = self.delays(num_frames);
+        let mut sequence: u32 = 0;
+
+        w.write_all(PNG_SIGNATURE)?;
+        for (i, (img, delay)) in frames.zip(delays).enumerate() {
+            let (width, height) = img.dimensions();
+            let mut png = Vec::new();
+            PngEncoder::new(&mut png).encode(&img, width, height, ColorType::Rgb8)?;
+            let chunks = png_chunks(&png)?;
+            if i == 0 {
+                let header = chunks.iter().find(|(kind, _)| kind == b"IHDR").ok_or(error::Error::ModelError)?;
+                write_chunk(&mut w, b"IHDR", header.1)?;
+                let mut control = Vec::new();
+                control.extend_from_slice(&(num_frames as u32).to_be_bytes());
+                // loop forever
+                control.extend_from_slice(&0u32.to_be_bytes());
+                write_chunk(&mut w, b"acTL", &control)?;
+            }
+
+            let mut control = Vec::new();
+            control.extend_from_slice(&sequence.to_be_bytes());
+            control.extend_from_slice(&width.to_be_bytes());
+            control.extend_from_slice(&height.to_be_bytes());
+            // x and y offsets
+            control.extend_from_slice(&[0; 8]);
+            control.extend_from_slice(&(delay.min(u16::MAX as u32) as u16).to_be_bytes());
+            control.extend_from_slice(&1000u16.to_be_bytes());
+            // no disposal, frames replace the whole canvas
+            control.extend_from_slice(&[0, 0]);
+            write_chunk(&mut w, b"fcTL", &control)?;
+            sequence += 1;
+
+            for (_, data) in chunks.iter().filter(|(kind, _)| kind == b"IDAT") {
+                if i == 0 {
+                    write_chunk(&mut w, b"IDAT", data)?;
+                } else {
+                    let mut frame_data = sequence.to_be_bytes().to_vec();
+                    frame_data.extend_from_slice(data);
+                    write_chunk(&mut w, b"fdAT", &frame_data)?;
+                    sequence += 1;
+                }
+            }
+        }
+        write_chunk(&mut w, b"IEND", &[])?;
+        Ok(())
+    }
+}
+
+// Creates the file at `path` and fills it with `write`
+fn write_file<F>(path: &Path, write: F) -> error::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> error::Result<()>,
+{
+    let mut file = BufWriter::new(File::create(path)?);
+    write(&mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+// Splits an encoded png into (type, data) chunks
+fn png_chunks(png: &[u8]) -> error::Result<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = Vec::new();
+    let mut rest = png.strip_prefix(PNG_SIGNATURE).ok_or(error::Error::ModelError)?;
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if rest.len() < length + 12 {
+            return Err(error::Error::ModelError);
+        }
+        let kind = [rest[4], rest[5], rest[6], rest[7]];
+        chunks.push((kind, &rest[8..8 + length]));
+        rest = &rest[length + 12..];
+    }
+    Ok(chunks)
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> error::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    w.write_all(&hasher.finalize().to_be_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, ImageFormat};
+
+    fn frames() -> Vec<RgbImage> {
+        [Rgb([0, 0, 0]), Rgb([200, 30, 90]), Rgb([10, 250, 120])].iter()
+            .map(|&color| RgbImage::from_fn(5, 3, |x, y| if x > y { color } else { Rgb([x as u8, y as u8, 7]) }))
+            .collect()
+    }
+
+    // (type, data) of every chunk after checking its CRC
+    fn checked_chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        let mut chunks = Vec::new();
+        let mut rest = png.strip_prefix(PNG_SIGNATURE).expect("png signature");
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + length]);
+            let crc = u32::from_be_bytes([rest[8 + length], rest[9 + length], rest[10 + length], rest[11 + length]]);
+            assert_eq!(crc, crc32fast::hash(&rest[4..8 + length]), "CRC of {:?}", kind);
+            chunks.push(([kind[0], kind[1], kind[2], kind[3]], data.to_vec()));
+            rest = &rest[12 + length..];
+        }
+        chunks
+    }
+
+    #[test]
+    fn apng_has_valid_chunks_and_frames() {
+        let mut png = Vec::new();
+        Animation::new(1).write_apng(&mut png, frames()).unwrap();
+        let chunks = checked_chunks(&png);
+
+        let control = &chunks.iter().find(|(kind, _)| kind == b"acTL").expect("acTL chunk").1;
+        assert_eq!(u32::from_be_bytes([control[0], control[1], control[2], control[3]]), 3);
+        let num_frame_controls = chunks.iter().filter(|(kind, _)| kind == b"fcTL").count();
+        assert_eq!(num_frame_controls, 3);
+
+        let sequence: Vec<u32> = chunks.iter()
+            .filter(|(kind, _)| kind == b"fcTL" || kind == b"fdAT")
+            .map(|(_, data)| u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+            .collect();
+        assert_eq!(sequence, (0..sequence.len() as u32).collect::<Vec<_>>());
+        assert_eq!(&chunks.first().unwrap().0, b"IHDR");
+        assert_eq!(&chunks.last().unwrap().0, b"IEND");
+
+        // decoders without APNG support show the first frame
+        let first = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap().into_rgb8();
+        assert_eq!(first, frames()[0]);
+    }
+
+    #[test]
+    fn apng_needs_a_frame() {
+        assert!(Animation::new(1).write_apng(Vec::new(), Vec::new()).is_err());
+    }
+}
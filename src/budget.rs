@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+// Limits on the search for a single shape.  The optimizers stop early and
+// return the best shape so far once either runs out.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Budget {
+    // calls to `Shape::error`
+    pub evaluations: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+impl Budget {
+    pub fn evaluations(evaluations: u64) -> Self {
+        Budget { evaluations: Some(evaluations), time: None }
+    }
+
+    pub fn time(time: Duration) -> Self {
+        Budget { evaluations: None, time: Some(time) }
+    }
+}
+
+// When a run of many steps stops, whichever comes first.  The default sets
+// no limit at all, so at least one has to be given before running.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RunLimit {
+    // steps, each adding a shape unless its search found none that helps
+    pub shapes: Option<u32>,
+    // root mean square error to stop at, as in `Model::errors`
+    pub error: Option<f32>,
+    pub time: Option<Duration>,
+}
+
+impl RunLimit {
+    pub fn shapes(shapes: u32) -> Self {
+        RunLimit { shapes: Some(shapes), ..RunLimit::default() }
+    }
+
+    pub fn error(error: f32) -> Self {
+        RunLimit { error: Some(error), ..RunLimit::default() }
+    }
+
+    pub fn time(time: Duration) -> Self {
+        RunLimit { time: Some(time), ..RunLimit::default() }
+    }
+
+    pub fn or_shapes(mut self, shapes: u32) -> Self {
+        self.shapes = Some(shapes);
+        self
+    }
+
+    pub fn or_error(mut self, error: f32) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    pub fn or_time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.shapes.is_none() && self.error.is_none() && self.time.is_none()
+    }
+
+    // Why a run that began at `started` should stop now, having taken
+    // `shapes` steps and reached `error`
+    pub fn reached(&self, shapes: u32, error: f32, started: Instant) -> Option<StopReason> {
+        if self.shapes.is_some_and(|limit| shapes >= limit) {
+            Some(StopReason::Shapes)
+        } else if self.error.is_some_and(|limit| error <= limit) {
+            Some(StopReason::Error)
+        } else if self.time.is_some_and(|limit| started.elapsed() >= limit) {
+            Some(StopReason::Time)
+        } else {
+            None
+        }
+    }
+
+    pub fn deadline(&self, started: Instant) -> Option<Instant> {
+        self.time.map(|time| started + time)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    Shapes,
+    Error,
+    Time,
+}
@@ -0,0 +1,328 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use image::ImageFormat;
+
+use minimalist2::{ShapeKind, Alpha, Metric, Optimizer, Annealing, Cooling, Genetic, Budget, RunLimit};
+use minimalist2::error::{Error, Result};
+
+pub const USAGE: &str = "\
+Usage: minimalist2 -i INPUT -o OUTPUT [options]
+
+Options:
+  -i, --input PATH       target image
+  -o, --output PATH      output image or .svg file, may be given several times
+  -n, --shapes N         number of shapes to add (default 50, or no limit when
+                         --target-error or --time is given); a step whose
+                         search is cut short adds nothing if it can't help
+      --target-error E   stop once the error falls to E
+      --time SECONDS     stop adding shapes after SECONDS
+  -m, --kind KIND        ellipse, rotated-ellipse, circle, triangle, rectangle,
+                         rotated-rectangle, bezier or polygon-N, or a comma
+                         separated list to mix kinds (default ellipse)
+  -a, --alpha A          shape alpha from 1 to 255, or auto (default 128)
+      --metric METRIC    error metric: rgb, cie76 or ciede2000 (default rgb)
+      --weights MAP      grayscale image whose bright areas get more detail, or
+                         'detail' to weight the target's detailed areas
+      --candidates N     random candidates per hill climb (default 1000)
+      --climbs N         hill climbs per shape (default 4)
+      --max-age N        failed mutations before a climb stops (default 100)
+      --optimizer NAME   hill-climb, anneal or genetic (default hill-climb)
+      --temperature T,T  start and end annealing temperature, in units of the
+                         error (default 0.05,0.0005)
+      --cooling NAME     annealing cooling, linear or exponential (default
+                         exponential)
+      --anneal-steps N   mutations tried per anneal (default 1000)
+      --population N     shapes per genetic generation (default 200)
+      --generations N    genetic generations per shape (default 20)
+      --step-evaluations N
+                         shapes scored at most per shape, across all workers
+      --step-time SECONDS
+                         seconds spent at most searching for each shape
+  -s, --seed N           seed for a reproducible run (default random)
+  -r, --resize N         optimize at most N pixels wide and high, then scale
+                         the output back up to about the input size
+  -j, --workers N        threads to search with (default one per core)
+      --frames PATTERN   save progress frames as images or .svg files, '{}' is
+                         replaced by the step
+      --animate PATH     save the construction as an animated .gif or .png
+      --every N          save a frame every N shapes (default 1)
+      --delay MS         milliseconds per animation frame (default 100)
+      --hold MS          extra milliseconds to show the last frame (default 2000)
+  -h, --help             print this message
+";
+
+#[derive(Debug)]
+pub struct Options {
+    pub input: PathBuf,
+    pub outputs: Vec<PathBuf>,
+    // when to stop adding shapes
+    pub limit: RunLimit,
+    pub kind: ShapeKind,
+    pub alpha: Alpha,
+    pub metric: Metric,
+    pub weights: Option<WeightMap>,
+    pub num_rand: u32,
+    pub num_climbs: u32,
+    pub max_age: u32,
+    pub optimizer: Optimizer,
+    pub budget: Budget,
+    pub seed: Option<u64>,
+    pub working_size: Option<u32>,
+    pub num_workers: Option<usize>,
+    pub frames: Option<String>,
+    pub animation: Option<PathBuf>,
+    pub frame_every: u32,
+    pub frame_delay: u32,
+    pub final_hold: u32,
+}
+
+#[derive(Debug)]
+pub enum WeightMap {
+    Image(PathBuf),
+    Detail,
+}
+
+// What the command line asks for
+#[derive(Debug)]
+pub enum Command {
+    Run(Box<Options>),
+    Help,
+}
+
+impl Options {
+    // the path of the progress frame saved after `step` shapes, if any
+    pub fn frame_path(&self, step: u32) -> Option<String> {
+        match &self.frames {
+            Some(pattern) if step % self.frame_every == 0 => Some(pattern.replace("{}", &step.to_string())),
+            _ => None,
+        }
+    }
+}
+
+// Parses the arguments following the program name
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command> {
+    let mut input = None;
+    let mut outputs = Vec::new();
+    let mut num_shapes = None;
+    let mut target_error = None;
+    let mut time = None;
+    let mut kind = ShapeKind::Ellipse;
+    let mut alpha = Alpha::Fixed(128);
+    let mut metric = Metric::Rgb;
+    let mut weights = None;
+    let mut num_rand = 1000;
+    let mut num_climbs = 4;
+    let mut max_age = 100;
+    let mut optimizer_name = "hill-climb".to_string();
+    let mut schedule = Annealing::default();
+    let mut schedule_given = false;
+    let mut genetic = Genetic::default();
+    let mut genetic_given = false;
+    let mut budget = Budget::default();
+    let mut seed = None;
+    let mut working_size = None;
+    let mut num_workers = None;
+    let mut frames = None;
+    let mut animation = None;
+    let mut frame_every = None;
+    let mut frame_delay = None;
+    let mut final_hold = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| argument_error(format!("{} needs a value", arg)));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-i" | "--input" => input = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => outputs.push(PathBuf::from(value()?)),
+            "-n" | "--shapes" => num_shapes = Some(positive(&arg, &value()?)?),
+            "--target-error" => target_error = Some(target(&arg, &value()?)?),
+            "--time" => time = Some(seconds(&arg, &value()?)?),
+            "-m" | "--kind" => kind = value()?.parse()?,
+            "-a" | "--alpha" => alpha = value()?.parse()?,
+            "--metric" => metric = value()?.parse()?,
+            "--weights" => weights = Some(match value()?.as_str() {
+                "detail" => WeightMap::Detail,
+                path => WeightMap::Image(PathBuf::from(path)),
+            }),
+            "--candidates" => num_rand = positive(&arg, &value()?)?,
+            "--climbs" => num_climbs = positive(&arg, &value()?)?,
+            "--max-age" => max_age = positive(&arg, &value()?)?,
+            "--optimizer" => optimizer_name = value()?,
+            "--temperature" => {
+                let (start, end) = temperatures(&value()?)?;
+                schedule.start_temperature = start;
+                schedule.end_temperature = end;
+                schedule_given = true;
+            }
+            "--cooling" => {
+                schedule.cooling = match value()?.as_str() {
+                    "linear" => Cooling::Linear,
+                    "exponential" => Cooling::Exponential,
+                    name => return Err(argument_error(format!("unknown cooling '{}'", name))),
+                };
+                schedule_given = true;
+            }
+            "--anneal-steps" => {
+                schedule.steps = positive(&arg, &value()?)?;
+                schedule_given = true;
+            }
+            "--population" => {
+                genetic.population = positive(&arg, &value()?)?;
+                genetic_given = true;
+            }
+            "--generations" => {
+                genetic.generations = number(&arg, &value()?)?;
+                genetic_given = true;
+            }
+            "--step-evaluations" => budget.evaluations = Some(positive(&arg, &value()?)?),
+            "--step-time" => budget.time = Some(seconds(&arg, &value()?)?),
+            "-s" | "--seed" => seed = Some(number(&arg, &value()?)?),
+            "-r" | "--resize" => working_size = Some(positive(&arg, &value()?)?),
+            "-j" | "--workers" => num_workers = Some(positive(&arg, &value()?)?),
+            "--frames" => frames = Some(value()?),
+            "--animate" => animation = Some(PathBuf::from(value()?)),
+            "--every" => frame_every = Some(positive(&arg, &value()?)?),
+            "--delay" => frame_delay = Some(number(&arg, &value()?)?),
+            "--hold" => final_hold = Some(number(&arg, &value()?)?),
+            _ => return Err(argument_error(format!("unknown argument '{}'", arg))),
+        }
+    }
+
+    let optimizer = match optimizer_name.as_str() {
+        "hill-climb" => Optimizer::HillClimb,
+        "anneal" => Optimizer::Anneal(schedule),
+        "genetic" => Optimizer::Genetic(genetic),
+        name => return Err(argument_error(format!("unknown optimizer '{}'", name))),
+    };
+    if schedule_given && !matches!(optimizer, Optimizer::Anneal(_)) {
+        return Err(argument_error("annealing options need --optimizer anneal".to_string()));
+    }
+    if genetic_given && !matches!(optimizer, Optimizer::Genetic(_)) {
+        return Err(argument_error("--population and --generations need --optimizer genetic".to_string()));
+    }
+    if schedule.cooling == Cooling::Exponential && schedule.end_temperature <= 0.0 {
+        return Err(argument_error("exponential cooling needs an end temperature above 0".to_string()));
+    }
+    if genetic.population < 2 {
+        return Err(argument_error("a genetic population needs at least 2 shapes".to_string()));
+    }
+
+    // without a limit on the error or time, stop after 50 shapes
+    let limit = RunLimit {
+        shapes: num_shapes.or(if target_error.is_none() && time.is_none() { Some(50) } else { None }),
+        error: target_error,
+        time,
+    };
+
+    let input = input.ok_or_else(|| argument_error("an input image is required".to_string()))?;
+    if outputs.is_empty() {
+        return Err(argument_error("at least one output is required".to_string()));
+    }
+    for output in &outputs {
+        check_output(output)?;
+    }
+    if let Some(pattern) = &frames {
+        if !pattern.contains("{}") {
+            return Err(argument_error(format!("frame pattern '{}' has no '{{}}' for the step", pattern)));
+        }
+        check_output(Path::new(pattern))?;
+    }
+    if let Some(path) = &animation {
+        check_animation(path)?;
+    } else if frame_delay.is_some() || final_hold.is_some() {
+        return Err(argument_error("--delay and --hold need an --animate path".to_string()));
+    }
+    if frame_every.is_some() && frames.is_none() && animation.is_none() {
+        return Err(argument_error("--every needs a --frames pattern or an --animate path".to_string()));
+    }
+
+    Ok(Command::Run(Box::new(Options {
+        input,
+        outputs,
+        limit,
+        kind,
+        alpha,
+        metric,
+        weights,
+        num_rand,
+        num_climbs,
+        max_age,
+        optimizer,
+        budget,
+        seed,
+        working_size,
+        num_workers,
+        frames,
+        animation,
+        frame_every: frame_every.unwrap_or(1),
+        frame_delay: frame_delay.unwrap_or(100),
+        final_hold: final_hold.unwrap_or(2000),
+    })))
+}
+
+fn argument_error(message: String) -> Error {
+    Error::ArgumentError(message)
+}
+
+fn number<T: FromStr>(arg: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| argument_error(format!("{} expects a number, got '{}'", arg, value)))
+}
+
+fn positive<T: FromStr + PartialOrd + Default>(arg: &str, value: &str) -> Result<T> {
+    let number: T = number(arg, value)?;
+    if number <= T::default() {
+        return Err(argument_error(format!("{} must be at least 1, got '{}'", arg, value)));
+    }
+    Ok(number)
+}
+
+fn seconds(arg: &str, value: &str) -> Result<Duration> {
+    let seconds: f64 = number(arg, value)?;
+    if !(seconds.is_finite() && seconds > 0.0) {
+        return Err(argument_error(format!("{} expects a positive number of seconds, got '{}'", arg, value)));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+fn target(arg: &str, value: &str) -> Result<f32> {
+    let error: f32 = number(arg, value)?;
+    if !(error.is_finite() && error >= 0.0) {
+        return Err(argument_error(format!("{} must not be negative, got '{}'", arg, value)));
+    }
+    Ok(error)
+}
+
+// Parses "START,END" with START at least END and neither negative
+fn temperatures(value: &str) -> Result<(f32, f32)> {
+    let invalid = || argument_error(format!("--temperature expects START,END, got '{}'", value));
+    let (start, end) = value.split_once(',').ok_or_else(invalid)?;
+    let (start, end): (f32, f32) = (start.trim().parse().map_err(|_| invalid())?, end.trim().parse().map_err(|_| invalid())?);
+    if !(start.is_finite() && end >= 0.0 && start >= end) {
+        return Err(argument_error(format!("temperatures must fall from START to END >= 0, got '{}'", value)));
+    }
+    Ok((start, end))
+}
+
+// Outputs are svg documents or any raster format the image crate can write
+fn check_output(path: &Path) -> Result<()> {
+    if is_svg(path) || ImageFormat::from_path(path).is_ok() {
+        Ok(())
+    } else {
+        Err(argument_error(format!("can't tell the output format of '{}'", path.display())))
+    }
+}
+
+fn check_animation(path: &Path) -> Result<()> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_ascii_lowercase();
+    match extension.as_str() {
+        "gif" | "png" | "apng" => Ok(()),
+        _ => Err(argument_error(format!("animations are .gif, .png or .apng files, got '{}'", path.display()))),
+    }
+}
+
+pub fn is_svg(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+}
@@ -1,5 +1,7 @@
 use std::error::Error as StdError;
 use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::result;
 
 use image::error;
@@ -7,24 +9,62 @@ use image::error;
 pub type Result<T> = result::Result<T, Error>;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     ModelError,
-    WriteError,
+    // an image couldn't be read, decoded or encoded
+    ImageError(error::ImageError),
+    // a file couldn't be read or written
+    IoError(io::Error),
+    // invalid command line arguments or configuration, with an explanation
+    ArgumentError(String),
+    // the error reading or writing the file at the path
+    FileError(PathBuf, Box<Error>),
 }
 
-impl StdError for Error {}
+impl Error {
+    // This error, as it happened reading or writing `path`
+    pub fn with_path<P: AsRef<Path>>(self, path: P) -> Self {
+        Error::FileError(path.as_ref().to_path_buf(), Box::new(self))
+    }
+}
+
+// For `map_err`, naming `path` in errors from reading or writing it
+pub fn at_path<E: Into<Error>>(path: &Path) -> impl FnOnce(E) -> Error + '_ {
+    move |err| err.into().with_path(path)
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::ImageError(err) => Some(err),
+            Error::IoError(err) => Some(err),
+            Error::FileError(_, err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::ModelError => write!(f, "Model Error"),
-            Error::WriteError => write!(f, "Write Error"),
+            Error::ImageError(err) => write!(f, "Image Error: {}", err),
+            Error::IoError(err) => write!(f, "IO Error: {}", err),
+            Error::ArgumentError(message) => write!(f, "Argument Error: {}", message),
+            Error::FileError(path, err) => write!(f, "{}: {}", path.display(), err),
         }
     }
 }
 
 impl From<error::ImageError> for Error {
-    fn from(_: error::ImageError) -> Self {
-        Error::ModelError
+    fn from(err: error::ImageError) -> Self {
+        Error::ImageError(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::IoError(err)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,27 @@
+extern crate image;
+extern crate rand;
+extern crate rand_distr;
+extern crate rand_chacha;
+extern crate dyn_clone;
+extern crate crc32fast;
+
+pub mod shape;
+pub mod model;
+pub mod optimize;
+pub mod util;
+pub mod error;
+pub mod svg;
+pub mod animation;
+pub mod metric;
+pub mod weights;
+pub mod budget;
+
+pub use model::{Model, ModelBuilder, Settings, ShapeRecord};
+pub use animation::Animation;
+pub use metric::Metric;
+pub use weights::Weights;
+pub use budget::{Budget, RunLimit, StopReason};
+pub use optimize::{Optimizer, Annealing, Cooling, Genetic, StepSize};
+pub use shape::{Shape, ShapeKind, Alpha};
+pub use shape::{Drawable, Mutatable, Rasterizable};
+pub use error::{Error, Result};
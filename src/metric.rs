@@ -0,0 +1,231 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use image::{Rgb, RgbImage};
+
+use crate::error;
+use crate::weights::{Weights, weight};
+
+// How the difference between the current image and the target is measured.
+// The error of an image is the root of the mean `difference` over its pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Metric {
+    // squared distance between rgb values, averaged over the channels
+    #[default]
+    Rgb,
+    // squared CIE76 ΔE, the euclidean distance in CIELAB
+    Cie76,
+    // squared CIEDE2000 ΔE, which corrects CIE76 for how the eye weighs
+    // lightness, chroma and hue
+    Ciede2000,
+}
+
+impl Metric {
+    pub fn difference(&self, pixel1: Rgb<u8>, pixel2: Rgb<u8>) -> f64 {
+        match self {
+            Metric::Rgb => {
+                let [r1, g1, b1] = pixel1.0;
+                let [r2, g2, b2] = pixel2.0;
+                let [dr, dg, db] = [
+                    ((r1 as i32) - (r2 as i32)).pow(2),
+                    ((g1 as i32) - (g2 as i32)).pow(2),
+                    ((b1 as i32) - (b2 as i32)).pow(2)
+                ];
+                (dr + dg + db) as f64 / 3.0
+            }
+            Metric::Cie76 => {
+                let ([l1, a1, b1], [l2, a2, b2]) = (lab(pixel1), lab(pixel2));
+                (l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)
+            }
+            Metric::Ciede2000 => ciede2000(lab(pixel1), lab(pixel2)).powi(2),
+        }
+    }
+
+    // Whether colors are compared in CIELAB rather than rgb
+    pub fn is_lab(&self) -> bool {
+        !matches!(self, Metric::Rgb)
+    }
+
+    // Weighted by `weights` when given, which have a mean of 1 so the count of
+    // pixels stays the normalizer
+    pub fn error(&self, img1: &RgbImage, img2: &RgbImage, weights: Option<&Weights>) -> f32 {
+        assert_eq!(img1.dimensions(), img2.dimensions());
+        let count = img1.dimensions().0 * img1.dimensions().1;
+        let mut error: f64 = 0.0;
+        for ((x, y, &pixel1), &pixel2) in img1.enumerate_pixels().zip(img2.pixels()) {
+            error += weight(weights, x, y) as f64 * self.difference(pixel1, pixel2);
+        }
+        (error / count as f64).sqrt() as f32
+    }
+}
+
+// Parses "rgb", "cie76" or "ciede2000"
+impl FromStr for Metric {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> error::Result<Self> {
+        match s {
+            "rgb" => Ok(Metric::Rgb),
+            "cie76" => Ok(Metric::Cie76),
+            "ciede2000" => Ok(Metric::Ciede2000),
+            _ => Err(error::Error::ArgumentError(format!("unknown metric '{}'", s))),
+        }
+    }
+}
+
+// D65 white point
+const WHITE: [f64; 3] = [0.95047, 1.0, 1.08883];
+const EPSILON: f64 = 6.0 / 29.0;
+
+// Linear intensity of each 8-bit sRGB value, since converting every pixel with
+// powf would dominate the error computation
+fn linear_table() -> &'static [f64; 256] {
+    static TABLE: OnceLock<[f64; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (value, linear) in table.iter_mut().enumerate() {
+            *linear = to_linear(value as f64 / 255.0);
+        }
+        table
+    })
+}
+
+fn to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// CIELAB coordinates of an sRGB pixel
+pub fn lab(pixel: Rgb<u8>) -> [f64; 3] {
+    let table = linear_table();
+    let [r, g, b] = pixel.0;
+    lab_from_linear([table[r as usize], table[g as usize], table[b as usize]])
+}
+
+// CIELAB coordinates of an sRGB color with channels from 0 to 1
+pub fn lab_from_unit(color: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = color;
+    lab_from_linear([to_linear(r), to_linear(g), to_linear(b)])
+}
+
+fn lab_from_linear([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let x = 0.4124564 * r + 0.3575761 * g + 0.1804375 * b;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = 0.0193339 * r + 0.1191920 * g + 0.9503041 * b;
+    let f = |t: f64| {
+        if t > EPSILON.powi(3) { t.cbrt() } else { t / (3.0 * EPSILON.powi(2)) + 4.0 / 29.0 }
+    };
+    let (fx, fy, fz) = (f(x / WHITE[0]), f(y / WHITE[1]), f(z / WHITE[2]));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+// The nearest sRGB pixel to CIELAB coordinates
+pub fn rgb_from_lab([l, a, b]: [f64; 3]) -> Rgb<u8> {
+    let fy = (l + 16.0) / 116.0;
+    let (fx, fz) = (fy + a / 500.0, fy - b / 200.0);
+    let f_inv = |t: f64| {
+        if t > EPSILON { t.powi(3) } else { 3.0 * EPSILON.powi(2) * (t - 4.0 / 29.0) }
+    };
+    let (x, y, z) = (WHITE[0] * f_inv(fx), WHITE[1] * f_inv(fy), WHITE[2] * f_inv(fz));
+    let r = 3.2404542 * x - 1.5371385 * y - 0.4985314 * z;
+    let g = -0.9692660 * x + 1.8760108 * y + 0.0415560 * z;
+    let b = 0.0556434 * x - 0.2040259 * y + 1.0572252 * z;
+    let channel = |value: f64| (255.0 * from_linear(value.clamp(0.0, 1.0))).round() as u8;
+    Rgb([channel(r), channel(g), channel(b)])
+}
+
+// CIEDE2000 color difference, following Sharma, Wu and Dalal (2005)
+pub fn ciede2000([l1, a1, b1]: [f64; 3], [l2, a2, b2]: [f64; 3]) -> f64 {
+    let pow7_25 = 25f64.powi(7);
+    let c_bar = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+    let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + pow7_25)).sqrt());
+    let (a1, a2) = ((1.0 + g) * a1, (1.0 + g) * a2);
+    let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+    let hue = |a: f64, b: f64| {
+        if a == 0.0 && b == 0.0 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) }
+    };
+    let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = if c1 * c2 == 0.0 {
+        0.0
+    } else if h2 - h1 > 180.0 {
+        h2 - h1 - 360.0
+    } else if h2 - h1 < -180.0 {
+        h2 - h1 + 360.0
+    } else {
+        h2 - h1
+    };
+    let delta_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+    let l_bar = (l1 + l2) / 2.0;
+    let c_bar = (c1 + c2) / 2.0;
+    let h_bar = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let cos = |degrees: f64| degrees.to_radians().cos();
+    let t = 1.0 - 0.17 * cos(h_bar - 30.0) + 0.24 * cos(2.0 * h_bar)
+        + 0.32 * cos(3.0 * h_bar + 6.0) - 0.20 * cos(4.0 * h_bar - 63.0);
+    let delta_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+    let r_c = 2.0 * (c_bar.powi(7) / (c_bar.powi(7) + pow7_25)).sqrt();
+    let s_l = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_bar;
+    let s_h = 1.0 + 0.015 * c_bar * t;
+    let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+    let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+    (l * l + c * c + h * h + r_t * c * h).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test pairs and expected ΔE from Sharma, Wu and Dalal (2005), table 1
+    const SHARMA: [([f64; 3], [f64; 3], f64); 12] = [
+        ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+        ([50.0, 3.1571, -77.2803], [50.0, 0.0, -82.7485], 2.8615),
+        ([50.0, 2.8361, -74.0200], [50.0, 0.0, -82.7485], 3.4412),
+        ([50.0, -1.3802, -84.2814], [50.0, 0.0, -82.7485], 1.0000),
+        ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+        ([50.0, 2.4900, -0.0010], [50.0, -2.4900, 0.0009], 7.1792),
+        ([50.0, 2.4900, -0.0010], [50.0, -2.4900, 0.0011], 7.2195),
+        ([50.0, 2.5000, 0.0], [73.0, 25.0, -18.0], 27.1492),
+        ([50.0, 2.5000, 0.0], [50.0, 3.2592, 0.3350], 1.0000),
+        ([60.2574, -34.0099, 36.2677], [60.4626, -34.1751, 39.4387], 1.2644),
+        ([22.7233, 20.0904, -46.6940], [23.0331, 14.9730, -42.5619], 2.0373),
+        ([2.0776, 0.0795, -1.1350], [0.9033, -0.0636, -0.5514], 0.9082),
+    ];
+
+    #[test]
+    fn ciede2000_matches_sharma() {
+        for &(lab1, lab2, expected) in SHARMA.iter() {
+            assert!((ciede2000(lab1, lab2) - expected).abs() < 1e-4, "{:?} {:?}", lab1, lab2);
+            assert!((ciede2000(lab2, lab1) - expected).abs() < 1e-4, "{:?} {:?}", lab2, lab1);
+        }
+    }
+
+    #[test]
+    fn ciede2000_of_equal_colors_is_zero() {
+        assert_eq!(ciede2000([50.0, 10.0, -20.0], [50.0, 10.0, -20.0]), 0.0);
+    }
+}
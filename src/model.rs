@@ -1,67 +1,637 @@
+use std::fs;
 use std::path::Path;
+use std::thread;
+use std::time::Instant;
 
 use image::Rgb;
 use image::RgbImage;
+use image::GrayImage;
 use image::ImageBuffer;
 
-use rand::{Rng, rngs::ThreadRng};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
-use dyn_clone::{clone_box};
-
-use crate::shape::{self, Shape, ShapeKind};
-use crate::optimize;
+use crate::shape::{Shape, ShapeKind, Alpha};
+use crate::optimize::{self, Optimizer, Evaluator};
+use crate::budget::{Budget, RunLimit, StopReason};
 use crate::util;
 use crate::error;
+use crate::svg;
+use crate::animation::Animation;
+use crate::metric::Metric;
+use crate::weights::Weights;
+
+// Everything needed to draw a shape the model chose again
+pub struct ShapeRecord {
+    pub shape: Box<dyn Shape>,
+    pub color: Rgb<u8>,
+    pub alpha: u8,
+}
+
+impl ShapeRecord {
+    pub fn draw_to_image(&self, img: &mut RgbImage) {
+        self.shape.draw_to_image(img, self.color, self.alpha);
+    }
+
+    pub fn svg(&self) -> String {
+        self.shape.svg(self.color, self.alpha)
+    }
+
+    pub fn scaled(&self, scale: f32) -> Self {
+        ShapeRecord { shape: self.shape.scaled(scale), color: self.color, alpha: self.alpha }
+    }
+}
+
+// Parameters of the search for each shape
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub kind: ShapeKind,
+    pub alpha: Alpha,
+    // how each random search's best shape is refined
+    pub optimizer: Optimizer,
+    // independent random searches, each followed by a hill climb or anneal
+    pub num_climbs: u32,
+    // failed mutations in a row before a hill climb gives up
+    pub max_age: u32,
+    // random candidates each hill climb starts from the best of
+    pub num_rand: u32,
+    // threads used to search for each shape: the random candidates are spread
+    // over all of them, the climbs after them over at most `num_climbs`.  The
+    // shapes found don't depend on it, so seeded runs match across machines.
+    pub num_workers: usize,
+    // limits on the search for each shape, on top of the counts above
+    pub budget: Budget,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            kind: ShapeKind::Ellipse,
+            alpha: Alpha::default(),
+            optimizer: Optimizer::default(),
+            num_climbs: 4,
+            max_age: 100,
+            num_rand: 1000,
+            num_workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            budget: Budget::default(),
+        }
+    }
+}
+
+// Configures a model before loading its target, e.g.
+// `Model::builder().kind(ShapeKind::Triangle).seed(7).open("mona.jpg")`
+#[derive(Debug, Clone, Default)]
+pub struct ModelBuilder {
+    settings: Settings,
+    seed: Option<u64>,
+    working_size: Option<u32>,
+    metric: Metric,
+    weights: Option<WeightMap>,
+    animation: Option<Animation>,
+}
+
+// Where a builder's weights come from, as they can only be made once the
+// target's working size is known
+#[derive(Debug, Clone)]
+enum WeightMap {
+    Image(GrayImage),
+    Detail,
+}
+
+impl ModelBuilder {
+    pub fn new() -> Self {
+        ModelBuilder::default()
+    }
+
+    pub fn settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn kind(mut self, kind: ShapeKind) -> Self {
+        self.settings.kind = kind;
+        self
+    }
+
+    pub fn alpha(mut self, alpha: Alpha) -> Self {
+        self.settings.alpha = alpha;
+        self
+    }
+
+    pub fn optimizer(mut self, optimizer: Optimizer) -> Self {
+        self.settings.optimizer = optimizer;
+        self
+    }
+
+    pub fn climbs(mut self, num_climbs: u32) -> Self {
+        self.settings.num_climbs = num_climbs;
+        self
+    }
+
+    pub fn max_age(mut self, max_age: u32) -> Self {
+        self.settings.max_age = max_age;
+        self
+    }
+
+    pub fn candidates(mut self, num_rand: u32) -> Self {
+        self.settings.num_rand = num_rand;
+        self
+    }
+
+    pub fn workers(mut self, num_workers: usize) -> Self {
+        self.settings.num_workers = num_workers.max(1);
+        self
+    }
+
+    // Stops each shape's search early once `budget` runs out.  Evaluation
+    // budgets keep runs reproducible, time budgets don't.
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.settings.budget = budget;
+        self
+    }
+
+    // the same target, seed and settings always give the same shapes, with any
+    // number of workers; without a seed a random one is picked
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Optimizes on a copy of the target shrunk so that neither side exceeds
+    // `max_size`, which is much faster for large photos.  The shapes are
+    // scaled back up when rendering the output.
+    pub fn working_size(mut self, max_size: u32) -> Self {
+        self.working_size = Some(max_size);
+        self
+    }
+
+    // how the error against the target is measured, rgb by default
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    // Weights each pixel's error by the brightness of `img`, which is stretched
+    // to the target's size, so bright areas get more detail
+    pub fn weights(mut self, img: GrayImage) -> Self {
+        self.weights = Some(WeightMap::Image(img));
+        self
+    }
+
+    // Weights each pixel's error by how detailed the target is around it
+    pub fn detail_weights(mut self) -> Self {
+        self.weights = Some(WeightMap::Detail);
+        self
+    }
+
+    // Records the construction into `animation`, starting with the background
+    pub fn animation(mut self, animation: Animation) -> Self {
+        self.animation = Some(animation);
+        self
+    }
+
+    pub fn open<P: AsRef<Path>>(self, path: P) -> error::Result<Model> {
+        let path = path.as_ref();
+        let target_img = image::open(path).map_err(error::at_path(path))?.into_rgb8();
+        self.build(target_img)
+    }
+
+    pub fn build(self, target_img: RgbImage) -> error::Result<Model> {
+        self.settings.kind.validate()?;
+        let original_size = target_img.dimensions();
+        let target_img = match self.working_size {
+            Some(max_size) => util::resize_to_fit(&target_img, max_size),
+            None => target_img,
+        };
+        // scaled from the working size rather than copied from the original,
+        // since rounding the working size changes the aspect ratio slightly
+        let output_size = scale_size(target_img.dimensions(), output_scale(target_img.dimensions(), original_size));
+        let weights = match &self.weights {
+            Some(WeightMap::Image(img)) => Some(Weights::from_image(img, target_img.dimensions())?),
+            Some(WeightMap::Detail) => Some(Weights::detail(&target_img)?),
+            None => None,
+        };
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut model = Model::from_target(target_img, output_size, self.settings, self.metric, weights, seed);
+        if let Some(animation) = self.animation {
+            model.start_animation(animation);
+        }
+        Ok(model)
+    }
+}
 
 pub struct Model {
-    pub background: Rgb<u8>,
+    background: Rgb<u8>,
     current_img: RgbImage,
     target_img: RgbImage,
-    pub size: (u32, u32),
+    // size the shapes are optimized at
+    size: (u32, u32),
+    // size finished images are rendered at: the working size scaled up until
+    // its larger side matches the original target's
+    output_size: (u32, u32),
+
+    shapes: Vec<ShapeRecord>,
+    errors: Vec<f32>,
+    // shapes scored while searching for each shape
+    evaluations: Vec<u64>,
+
+    settings: Settings,
+    // fixed for the model's lifetime, since the errors are measured with them
+    metric: Metric,
+    weights: Option<Weights>,
+    seed: u64,
+    rng: ChaCha8Rng,
 
-    pub shapes: Vec<Box<dyn Shape>>,
-    pub errors: Vec<f32>,
-    // add tracking vectors for shapes, colors, scores
-    // add multithreading?
+    animation: Option<Animation>,
 }
 
 impl Model {
+    pub fn builder() -> ModelBuilder {
+        ModelBuilder::new()
+    }
+
+    // A model with the default settings and a random seed
     pub fn new<P: AsRef<Path>>(path: P) -> error::Result<Self> {
-        let target_img = image::open(path)?.into_rgb8();
+        Model::builder().open(path)
+    }
+
+    fn from_target(target_img: RgbImage, output_size: (u32, u32), settings: Settings, metric: Metric, weights: Option<Weights>, seed: u64) -> Self {
         let size = target_img.dimensions();
         let background = util::average_image_color(&target_img);
         let current_img = ImageBuffer::from_fn(size.0, size.1, |_x, _y| {
             background
         });
         let shapes = Vec::new();
-        let errors = vec![util::mean_square_error(&current_img, &target_img)];
+        let errors = vec![metric.error(&current_img, &target_img, weights.as_ref())];
 
-        let model = Model {
+        Model {
             background,
             current_img,
             target_img,
             size,
+            output_size,
             shapes,
             errors,
-        };
-        Ok(model)
+            evaluations: Vec::new(),
+            settings,
+            metric,
+            weights,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            animation: None,
+        }
     }
-    pub fn step(&mut self, kind: &ShapeKind, num_climbs: u32, max_age: u32, num_rand: u32) {
-        let (shape, error) = self.next_shape(kind, num_climbs, max_age, num_rand);
-        shape.draw_best_color(&mut self.current_img, &self.target_img);
-        self.shapes.push(shape);
+
+    // Searches for the shape that lowers the error the most with the
+    // settings' optimizer, draws it and returns it.  Adds nothing when the
+    // search, cut short by its budget, found no shape that lowers the error.
+    pub fn step(&mut self) -> Option<&ShapeRecord> {
+        self.step_until(None)
+    }
+
+    // Like `step`, but the search also stops at `deadline`
+    pub fn step_until(&mut self, deadline: Option<Instant>) -> Option<&ShapeRecord> {
+        let (shape, error) = self.next_shape(deadline);
+        if error >= self.error() {
+            return None;
+        }
+        let color = shape.draw_best_color(&mut self.current_img, &self.target_img, self.metric, self.weights.as_ref());
+        let alpha = shape.alpha();
+        self.shapes.push(ShapeRecord { shape, color, alpha });
         self.errors.push(error);
+        if let Some(animation) = &mut self.animation {
+            animation.record(self.shapes.len());
+        }
+        self.shapes.last()
+    }
+
+    // Takes `num_steps` steps, which add a shape each unless a budget keeps
+    // one from lowering the error
+    pub fn run(&mut self, num_steps: u32) {
+        for _ in 0..num_steps {
+            self.step();
+        }
+    }
 
+    // Steps until `limit` is reached and says which part of it was, where a
+    // shape limit counts steps.  A time limit also cuts short the search for
+    // the last shape.  Fails for a limit with nothing set, which would never
+    // be reached.
+    pub fn run_until(&mut self, limit: &RunLimit) -> error::Result<StopReason> {
+        self.run_until_with(limit, |_| Ok(()))
     }
 
-    fn next_shape(&mut self, kind: &ShapeKind, num_climbs: u32, max_age: u32, num_rand: u32) -> (Box<dyn Shape>, f32) {
-        let mut rng = rand::thread_rng();
-        optimize::best_random_hill_climb(kind, num_climbs, max_age, num_rand, &self.current_img, &self.target_img, &mut rng)
+    // Like `run_until`, calling `on_step` after every shape is added, e.g. to
+    // report progress.  An error from `on_step` stops the run.
+    pub fn run_until_with<F>(&mut self, limit: &RunLimit, mut on_step: F) -> error::Result<StopReason>
+    where
+        F: FnMut(&Model) -> error::Result<()>,
+    {
+        if limit.is_unlimited() {
+            return Err(error::Error::ArgumentError("a run needs a shape, error or time limit".to_string()));
+        }
+        let started = Instant::now();
+        let deadline = limit.deadline(started);
+        let mut num_steps = 0;
+        loop {
+            if let Some(reason) = limit.reached(num_steps, self.error(), started) {
+                return Ok(reason);
+            }
+            num_steps += 1;
+            if self.step_until(deadline).is_some() {
+                on_step(self)?;
+            }
+        }
+    }
+
+    fn next_shape(&mut self, deadline: Option<Instant>) -> (Box<dyn Shape>, f32) {
+        let settings = &self.settings;
+        let mut evaluator = Evaluator::new(&self.current_img, &self.target_img, self.error(), self.metric, self.weights.as_ref())
+            .with_budget(&settings.budget);
+        if let Some(deadline) = deadline {
+            evaluator = evaluator.with_deadline(deadline);
+        }
+        let result = match &settings.optimizer {
+            Optimizer::HillClimb => optimize::best_random_hill_climb(
+                &settings.kind, settings.alpha,
+                settings.num_climbs, settings.max_age, settings.num_rand, settings.num_workers,
+                &evaluator, &mut self.rng
+            ),
+            Optimizer::Anneal(schedule) => optimize::best_random_anneal(
+                &settings.kind, settings.alpha,
+                settings.num_climbs, schedule, settings.num_rand, settings.num_workers,
+                &evaluator, &mut self.rng
+            ),
+            Optimizer::Genetic(genetic) => optimize::genetic(
+                &settings.kind, settings.alpha,
+                genetic, settings.num_workers,
+                &evaluator, &mut self.rng
+            ),
+        };
+        self.evaluations.push(evaluator.evaluations());
+        result
+    }
+
+    // Records the current image, and from now on the image after every
+    // `animation.frame_every` shapes.  Frames are at the working size.
+    pub fn start_animation(&mut self, mut animation: Animation) {
+        animation.finish(self.shapes.len());
+        self.animation = Some(animation);
+    }
+
+    pub fn animation(&self) -> Option<&Animation> {
+        self.animation.as_ref()
+    }
+
+    pub fn take_animation(&mut self) -> Option<Animation> {
+        self.animation.take()
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    // settings can be changed between steps, e.g. to switch shape kinds; a new
+    // kind should pass `ShapeKind::validate`
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    pub fn weights(&self) -> Option<&Weights> {
+        self.weights.as_ref()
+    }
+
+    pub fn background(&self) -> Rgb<u8> {
+        self.background
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn output_size(&self) -> (u32, u32) {
+        self.output_size
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn shapes(&self) -> &[ShapeRecord] {
+        &self.shapes
+    }
+
+    // the error before any shape followed by the error after each one
+    pub fn errors(&self) -> &[f32] {
+        &self.errors
+    }
+
+    // how many shapes were scored for each step, including steps that added
+    // no shape
+    pub fn evaluations(&self) -> &[u64] {
+        &self.evaluations
+    }
+
+    pub fn error(&self) -> f32 {
+        *self.errors.last().expect("errors always holds the initial error")
+    }
+
+    pub fn current_img(&self) -> &RgbImage {
+        &self.current_img
+    }
+
+    pub fn target_img(&self) -> &RgbImage {
+        &self.target_img
+    }
+
+    // the kind that won each step, useful when stepping with ShapeKind::Any
+    pub fn kinds(&self) -> Vec<ShapeKind> {
+        self.shapes.iter().map(|record| record.shape.kind()).collect()
     }
 
     // FOR TESTING PURPOSES
     pub fn save_current_img<P: AsRef<Path>>(&self, path: P) -> error::Result<()> {
-        self.current_img.save(path)?;
-        Ok(())
+        let path = path.as_ref();
+        self.current_img.save(path).map_err(error::at_path(path))
+    }
+
+    // Replays the recorded shapes over the background.  At the model's size
+    // this reproduces the current image exactly.
+    pub fn render(&self, width: u32, height: u32) -> RgbImage {
+        let mut img = ImageBuffer::from_pixel(width, height, self.background);
+        for record in &self.shapes {
+            record.draw_to_image(&mut img);
+        }
+        img
+    }
+
+    // Replays the recorded shapes with their geometry scaled by `scale`, so a
+    // model optimized at a small size can be rendered at a large one.
+    pub fn render_scaled(&self, scale: f32) -> RgbImage {
+        self.render_shapes(scale_size(self.size, scale), scale)
+    }
+
+    // Renders at the output size, scaling the shapes up when the model was
+    // optimized at a smaller working size
+    pub fn render_output(&self) -> RgbImage {
+        self.render_shapes(self.output_size, output_scale(self.size, self.output_size))
+    }
+
+    fn render_shapes(&self, (width, height): (u32, u32), scale: f32) -> RgbImage {
+        let mut img = ImageBuffer::from_pixel(width, height, self.background);
+        for record in &self.shapes {
+            record.scaled(scale).draw_to_image(&mut img);
+        }
+        img
+    }
+
+    pub fn save_scaled_img<P: AsRef<Path>>(&self, path: P, scale: f32) -> error::Result<()> {
+        let path = path.as_ref();
+        self.render_scaled(scale).save(path).map_err(error::at_path(path))
+    }
+
+    pub fn save_output_img<P: AsRef<Path>>(&self, path: P) -> error::Result<()> {
+        let path = path.as_ref();
+        self.render_output().save(path).map_err(error::at_path(path))
+    }
+
+    pub fn to_svg(&self) -> String {
+        let mut body = String::new();
+        for record in &self.shapes {
+            body += &record.svg();
+            body += "\n";
+        }
+        svg::document(self.size, self.output_size, self.background, &body)
+    }
+
+    pub fn save_svg<P: AsRef<Path>>(&self, path: P) -> error::Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_svg()).map_err(error::at_path(path))
+    }
+
+    // The images after each of `steps` shapes at the model's size, replayed
+    // from the records one frame at a time
+    pub fn images_after<'a>(&'a self, steps: &'a [usize]) -> impl ExactSizeIterator<Item = RgbImage> + 'a {
+        let (width, height) = self.size;
+        let mut img = ImageBuffer::from_pixel(width, height, self.background);
+        let mut drawn = 0;
+        steps.iter().map(move |&step| {
+            if step < drawn {
+                img = ImageBuffer::from_pixel(width, height, self.background);
+                drawn = 0;
+            }
+            for record in &self.shapes[drawn..step] {
+                record.draw_to_image(&mut img);
+            }
+            drawn = step;
+            img.clone()
+        })
+    }
+
+    // Saves the recorded animation, ending on the current image, as a GIF or
+    // an APNG depending on the extension
+    pub fn save_animation<P: AsRef<Path>>(&mut self, path: P) -> error::Result<()> {
+        let step = self.shapes.len();
+        let animation = self.animation.as_mut().ok_or_else(|| {
+            error::Error::ArgumentError("the model isn't recording an animation".to_string())
+        })?;
+        animation.finish(step);
+        let animation = self.animation.as_ref().expect("the animation was just finished");
+        animation.save(path, self.images_after(animation.steps()))
+    }
+}
+
+// How much larger `output_size` is than `size`, going by the larger sides,
+// which shrinking to a working size keeps in proportion
+fn output_scale(size: (u32, u32), output_size: (u32, u32)) -> f32 {
+    output_size.0.max(output_size.1) as f32 / size.0.max(size.1) as f32
+}
+
+fn scale_size((width, height): (u32, u32), scale: f32) -> (u32, u32) {
+    ((width as f32 * scale).round() as u32, (height as f32 * scale).round() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimize::{Annealing, Genetic};
+
+    fn target(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| Rgb([(x * 7 % 256) as u8, (y * 11 % 256) as u8, ((x + y) * 3 % 256) as u8]))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn output_size_keeps_the_working_aspect_ratio() {
+        let model = Model::builder().working_size(33).seed(1).build(target(100, 37)).unwrap();
+        assert_eq!(model.size(), (33, 12));
+        assert_eq!(model.output_size(), (100, 36));
+        assert_eq!(model.render_output().dimensions(), (100, 36));
+        assert!(model.to_svg().contains("width=\"100\" height=\"36\" viewBox=\"0 0 33 12\""));
+
+        let model = Model::builder().working_size(33).seed(1).build(target(37, 100)).unwrap();
+        assert_eq!(model.output_size(), (36, 100));
+    }
+
+    #[test]
+    fn errors_never_rise_under_a_tiny_budget() {
+        let mut model = Model::builder()
+            .kind(ShapeKind::Triangle)
+            .working_size(32)
+            .climbs(1)
+            .budget(Budget::evaluations(2))
+            .seed(3)
+            .build(target(32, 32))
+            .unwrap();
+        model.run(40);
+        assert!(model.errors().windows(2).all(|pair| pair[1] < pair[0]));
+        assert_eq!(model.errors().len(), model.shapes().len() + 1);
+        assert_eq!(model.evaluations().len(), 40);
+    }
+
+    #[test]
+    fn every_optimizer_keeps_the_evaluation_budget() {
+        let optimizers = [Optimizer::HillClimb, Optimizer::Anneal(Annealing::default()), Optimizer::Genetic(Genetic::default())];
+        for optimizer in optimizers {
+            for budget in [1, 3, 50, 2500] {
+                let mut model = Model::builder()
+                    .working_size(32)
+                    .optimizer(optimizer)
+                    .budget(Budget::evaluations(budget))
+                    .seed(5)
+                    .build(target(32, 32))
+                    .unwrap();
+                model.step();
+                assert!(model.evaluations()[0] <= budget, "{:?} scored {} shapes for a budget of {}", optimizer, model.evaluations()[0], budget);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_shapes_for_any_number_of_workers() {
+        let run = |num_workers: usize| {
+            let mut model = Model::builder()
+                .working_size(32)
+                .workers(num_workers)
+                .seed(11)
+                .build(target(32, 32))
+                .unwrap();
+            model.run(3);
+            model
+        };
+        let (one, four) = (run(1), run(4));
+        assert_eq!(one.current_img(), four.current_img());
+        assert_eq!(one.errors(), four.errors());
+    }
+
+    #[test]
+    fn render_at_the_working_size_matches_the_current_image() {
+        let mut model = Model::builder().working_size(32).seed(13).build(target(32, 24)).unwrap();
+        model.run(3);
+        let (width, height) = model.size();
+        assert_eq!(&model.render(width, height), model.current_img());
+    }
+}
@@ -1,41 +1,287 @@
+use std::thread;
+use std::time::Instant;
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use image::RgbImage;
-use rand::{Rng, rngs::ThreadRng};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use dyn_clone::{clone_box};
 
-use crate::shape::{Shape, ShapeKind};
-use crate::shape::{Rasterizable, Drawable, Mutatable};
-use crate::shape::{Ellipse};
+use crate::shape::{Shape, ShapeKind, Alpha};
+use crate::metric::Metric;
+use crate::weights::Weights;
+use crate::budget::Budget;
+
+// How the temperature falls over an annealing run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cooling {
+    Linear,
+    // falls by the same factor every step, spending longer at low temperatures
+    Exponential,
+}
+
+// A simulated annealing schedule.  Temperatures are in units of the error, so
+// at temperature t a mutation that worsens the error by t is accepted with
+// probability 1/e.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Annealing {
+    pub start_temperature: f32,
+    pub end_temperature: f32,
+    // mutations tried per run
+    pub steps: u32,
+    pub cooling: Cooling,
+}
+
+impl Annealing {
+    pub fn temperature(&self, step: u32) -> f32 {
+        let progress = step as f32 / (self.steps.max(2) - 1) as f32;
+        match self.cooling {
+            Cooling::Linear => self.start_temperature + (self.end_temperature - self.start_temperature) * progress,
+            Cooling::Exponential => self.start_temperature * (self.end_temperature / self.start_temperature).powf(progress),
+        }
+    }
+}
+
+impl Default for Annealing {
+    fn default() -> Self {
+        Annealing {
+            start_temperature: 0.05,
+            end_temperature: 0.0005,
+            steps: 1000,
+            cooling: Cooling::Exponential,
+        }
+    }
+}
+
+// Settings of the genetic optimizer.  Elites aren't scored again, so a run
+// evaluates `population + generations * (population - elites)` shapes in all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Genetic {
+    pub population: u32,
+    pub generations: u32,
+    // best shapes carried over unchanged into the next generation
+    pub elites: u32,
+    // shapes competing for each parent slot; larger means stronger selection
+    pub tournament: u32,
+    // chance a child mixes two parents of the same kind rather than copying one
+    pub crossover_rate: f32,
+}
+
+impl Default for Genetic {
+    fn default() -> Self {
+        Genetic {
+            population: 200,
+            generations: 20,
+            elites: 2,
+            tournament: 3,
+            crossover_rate: 0.7,
+        }
+    }
+}
+
+// How each step's shape is searched for
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Optimizer {
+    // random search followed by a hill climb that stops after `max_age`
+    // failed mutations in a row
+    #[default]
+    HillClimb,
+    // random search followed by simulated annealing, which can accept worse
+    // shapes early on to escape local minima
+    Anneal(Annealing),
+    // a population of random shapes evolved by selection, crossover and
+    // mutation
+    Genetic(Genetic),
+}
 
-use crate::util::{best_color_in_shape};
+// How far mutations move a shape, adapted by the 1/5 success rule: the rate
+// grows after a mutation that lowers the error and shrinks after one that
+// doesn't, settling where about a fifth of mutations succeed.  Steps are
+// large while the shape is far from a fit and fine once it's close.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepSize {
+    rate: f32,
+    min_rate: f32,
+    max_rate: f32,
+}
+
+impl StepSize {
+    const GROWTH: f32 = 1.5;
+
+    // Starts at 1/64 of the larger side, so a step moves shapes about as far
+    // relative to the image whatever its size
+    pub fn new((width, height): (u32, u32)) -> Self {
+        let size = width.max(height).max(1) as f32;
+        let max_rate = (size / 4.0).max(1.0);
+        StepSize { rate: (size / 64.0).clamp(1.0, max_rate), min_rate: 1.0, max_rate }
+    }
 
-pub fn best_random_shape(kind: &ShapeKind, num_rand: u32, source: &RgbImage, target: &RgbImage, mut rng: &mut ThreadRng) -> (Box<dyn Shape>, f32) {
-    let dimensions = source.dimensions();
-    let mut shape: Box<dyn Shape> = kind.random(dimensions, &mut rng);
-    let mut error: f32 = shape.error(&source, &target);
+    // standard deviation of a mutation's step, in pixels
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    // One success multiplies the rate by GROWTH and four failures divide it by
+    // GROWTH, which balances at one success in five
+    pub fn update(&mut self, improved: bool) {
+        let factor = if improved { Self::GROWTH } else { Self::GROWTH.powf(-0.25) };
+        self.rate = (self.rate * factor).clamp(self.min_rate, self.max_rate);
+    }
+}
+
+// Everything needed to score a shape drawn over `source`, plus the budget the
+// scoring is limited by.  `evaluations` counts every call to `error`.
+pub struct Evaluator<'a> {
+    pub source: &'a RgbImage,
+    pub target: &'a RgbImage,
+    // current error between source and target
+    pub source_error: f32,
+    pub metric: Metric,
+    pub weights: Option<&'a Weights>,
+    evaluations: AtomicU64,
+    max_evaluations: Option<u64>,
+    deadline: Option<Instant>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(source: &'a RgbImage, target: &'a RgbImage, source_error: f32, metric: Metric, weights: Option<&'a Weights>) -> Self {
+        Evaluator {
+            source,
+            target,
+            source_error,
+            metric,
+            weights,
+            evaluations: AtomicU64::new(0),
+            max_evaluations: None,
+            deadline: None,
+        }
+    }
+
+    // Limits the evaluations and time from now on
+    pub fn with_budget(mut self, budget: &Budget) -> Self {
+        self.max_evaluations = budget.evaluations;
+        if let Some(time) = budget.time {
+            self = self.with_deadline(Instant::now() + time);
+        }
+        self
+    }
+
+    // Stops by `deadline` at the latest
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(self.deadline.map_or(deadline, |current| current.min(deadline)));
+        self
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.source.dimensions()
+    }
+
+    pub fn error(&self, shape: &dyn Shape) -> f32 {
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        shape.error(self.source, self.target, self.source_error, self.metric, self.weights)
+    }
+
+    pub fn evaluations(&self) -> u64 {
+        self.evaluations.load(Ordering::Relaxed)
+    }
+
+    // evaluations left in the budget, if it limits them
+    pub fn remaining(&self) -> Option<u64> {
+        self.max_evaluations.map(|max| max.saturating_sub(self.evaluations()))
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.max_evaluations.is_some_and(|max| self.evaluations() >= max)
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    // An evaluator for one of `num_jobs` jobs, with its share of the
+    // evaluations left.  Splitting the count up front keeps evaluation budgets
+    // deterministic however the jobs are scheduled.
+    fn split(&self, num_jobs: usize, job: usize) -> Evaluator<'a> {
+        let mut evaluator = Evaluator::new(self.source, self.target, self.source_error, self.metric, self.weights);
+        evaluator.deadline = self.deadline;
+        evaluator.max_evaluations = self.max_evaluations.map(|max| {
+            let (left, num_jobs, job) = (max.saturating_sub(self.evaluations()), num_jobs as u64, job as u64);
+            left / num_jobs + if job < left % num_jobs { 1 } else { 0 }
+        });
+        evaluator
+    }
+}
+
+// Splits the `num_rand` random candidates into jobs of CANDIDATES_PER_JOB,
+// which run across `num_workers` threads
+pub fn best_random_shape<R: Rng>(
+    kind: &ShapeKind, alpha: Alpha,
+    num_rand: u32, num_workers: usize,
+    evaluator: &Evaluator, rng: &mut R
+) -> (Box<dyn Shape>, f32) {
+    random_searches(kind, alpha, 1, num_rand, num_workers, evaluator, rng)
+        .pop()
+        .expect("one search")
+}
+
+// random candidates scored by each job of a random search; fixed so the jobs
+// don't depend on the number of workers
+const CANDIDATES_PER_JOB: u32 = 100;
+
+// The best of `num_rand` random shapes for each of `num_searches` searches.
+// Every search is split into jobs of CANDIDATES_PER_JOB, and the jobs of all
+// the searches share the `num_workers` threads.  Both counts are cut to fit
+// an evaluation budget, so no job starts without an evaluation to spend.
+#[allow(clippy::too_many_arguments)]
+fn random_searches<R: Rng>(
+    kind: &ShapeKind, alpha: Alpha,
+    num_searches: u32, num_rand: u32, num_workers: usize,
+    evaluator: &Evaluator, rng: &mut R
+) -> Vec<(Box<dyn Shape>, f32)> {
+    let (num_searches, num_rand) = fit_budget(num_searches, num_rand, evaluator.remaining());
+    let jobs_per_search = num_rand.div_ceil(CANDIDATES_PER_JOB).max(1) as usize;
+    let num_jobs = num_searches.max(1) as usize * jobs_per_search;
+    let mut results = run_jobs(num_jobs, num_workers, evaluator, rng, |job, evaluator, rng| {
+        let num_rand = share(num_rand, jobs_per_search, job % jobs_per_search);
+        random_search(kind, alpha, num_rand, evaluator, rng)
+    }).into_iter();
+    let mut searches = Vec::with_capacity(num_jobs / jobs_per_search);
+    while results.len() > 0 {
+        searches.push(best(results.by_ref().take(jobs_per_search)));
+    }
+    searches
+}
+
+// Always scores at least one shape, so there is something to return even
+// when the budget is spent
+fn random_search<R: Rng>(kind: &ShapeKind, alpha: Alpha, num_rand: u32, evaluator: &Evaluator, rng: &mut R) -> (Box<dyn Shape>, f32) {
+    let dimensions = evaluator.dimensions();
+    let mut shape: Box<dyn Shape> = kind.random(dimensions, alpha, rng);
+    let mut error: f32 = evaluator.error(&*shape);
     for _ in 1..num_rand {
-        let new_shape = kind.random(dimensions, &mut rng);
-        let new_error = new_shape.error(&source, &target);
+        if evaluator.is_exhausted() {
+            break;
+        }
+        let new_shape = kind.random(dimensions, alpha, rng);
+        let new_error = evaluator.error(&*new_shape);
         if new_error < error {
             shape = new_shape;
             error = new_error;
         }
     }
-    println!("best random shape: {}, {}", shape, error);
     (shape, error)
 }
 
-pub fn hill_climb(init_shape: Box<dyn Shape>, init_error: f32, max_age: u32, source: &RgbImage, target: &RgbImage, mut rng: &mut ThreadRng) -> (Box<dyn Shape>, f32) {
-    let dimensions = source.dimensions();
+pub fn hill_climb<R: Rng>(init_shape: Box<dyn Shape>, init_error: f32, max_age: u32, evaluator: &Evaluator, rng: &mut R) -> (Box<dyn Shape>, f32) {
+    let dimensions = evaluator.dimensions();
     let mut shape = init_shape;
     let mut error = init_error;
     let mut age = 0;
-    let mut steps = 0;
-    while age < max_age {
+    let mut step_size = StepSize::new(dimensions);
+    while age < max_age && !evaluator.is_exhausted() {
         // println!("current age: {}", age);
         let mut new_shape = clone_box(&*shape);
-        new_shape.mutate(dimensions, &mut rng);
-        let new_error = new_shape.error(&source, &target);
+        new_shape.mutate(dimensions, step_size.rate(), rng);
+        let new_error = evaluator.error(&*new_shape);
         // println!("new_error: {}", new_error);
+        step_size.update(new_error < error);
         if new_error < error {
             shape = new_shape;
             error = new_error;
@@ -43,48 +289,313 @@ pub fn hill_climb(init_shape: Box<dyn Shape>, init_error: f32, max_age: u32, sou
         } else {
             age += 1;
         }
-        steps += 1;
     }
-    println!("steps: {}", steps);
-    println!("hill climb: {}, {}", shape, error);
     (shape, error)
 }
 
-pub fn best_hill_climb(
+// Simulated annealing from `init_shape`.  Mutations that lower the error are
+// always taken and ones that raise it by `delta` with probability
+// exp(-delta / temperature).  Returns the best shape seen.
+pub fn anneal<R: Rng>(init_shape: Box<dyn Shape>, init_error: f32, schedule: &Annealing, evaluator: &Evaluator, rng: &mut R) -> (Box<dyn Shape>, f32) {
+    let dimensions = evaluator.dimensions();
+    let mut best_shape = clone_box(&*init_shape);
+    let mut best_error = init_error;
+    let mut shape = init_shape;
+    let mut error = init_error;
+    let rate = StepSize::new(dimensions).rate();
+    for step in 0..schedule.steps {
+        if evaluator.is_exhausted() {
+            break;
+        }
+        let temperature = schedule.temperature(step);
+        let mut new_shape = clone_box(&*shape);
+        new_shape.mutate(dimensions, rate, rng);
+        let new_error = evaluator.error(&*new_shape);
+        let delta = new_error - error;
+        if delta < 0.0 || (temperature > 0.0 && rng.gen::<f32>() < (-delta / temperature).exp()) {
+            shape = new_shape;
+            error = new_error;
+            if error < best_error {
+                best_shape = clone_box(&*shape);
+                best_error = error;
+            }
+        }
+    }
+    (best_shape, best_error)
+}
+
+pub fn best_hill_climb<R: Rng>(
     init_shape: Box<dyn Shape>, init_error: f32,
     num_climbs: u32, max_age: u32, 
-    source: &RgbImage, target: &RgbImage,
-    mut rng: &mut ThreadRng
+    evaluator: &Evaluator, rng: &mut R
 ) -> (Box<dyn Shape>, f32) {
     let mut shape = clone_box(&*init_shape);
     let mut error = init_error;
     for _ in 0..num_climbs {
-        let (new_shape, new_error) = hill_climb(clone_box(&*init_shape), init_error, max_age, &source, &target, &mut rng);
+        let (new_shape, new_error) = hill_climb(clone_box(&*init_shape), init_error, max_age, evaluator, rng);
         if new_error < error {
             shape = new_shape;
             error = new_error;
         }
     }
-    println!("best hill climb: {}, {}", shape, error);
     (shape, error)
 }
 
-pub fn best_random_hill_climb(
-    kind: &ShapeKind,
-    num_climbs: u32, max_age: u32, num_rand: u32,
-    source: &RgbImage, target: &RgbImage,
-    mut rng: &mut ThreadRng
+// Runs `num_climbs` independent random searches followed by hill climbs,
+// spread across `num_workers` threads
+#[allow(clippy::too_many_arguments)]
+pub fn best_random_hill_climb<R: Rng>(
+    kind: &ShapeKind, alpha: Alpha,
+    num_climbs: u32, max_age: u32, num_rand: u32, num_workers: usize,
+    evaluator: &Evaluator, rng: &mut R
 ) -> (Box<dyn Shape>, f32) {
-    let (init_shape, init_error) = best_random_shape(&kind, num_rand, &source, &target, &mut rng);
-    let (mut shape, mut error) = hill_climb(init_shape, init_error, max_age, &source, &target, &mut rng);
-    for _ in 1..num_climbs {
-        let (init_shape, init_error) = best_random_shape(&kind, num_rand, &source, &target, &mut rng);
-        let (new_shape, new_error) =  hill_climb(init_shape, init_error, max_age, &source, &target, &mut rng);
+    best_random_local_search(kind, alpha, num_climbs, num_rand, num_workers, evaluator, rng, |shape, error, evaluator, rng| {
+        hill_climb(shape, error, max_age, evaluator, rng)
+    })
+}
+
+// Like `best_random_hill_climb`, but refines each random search's shape by
+// simulated annealing
+#[allow(clippy::too_many_arguments)]
+pub fn best_random_anneal<R: Rng>(
+    kind: &ShapeKind, alpha: Alpha,
+    num_climbs: u32, schedule: &Annealing, num_rand: u32, num_workers: usize,
+    evaluator: &Evaluator, rng: &mut R
+) -> (Box<dyn Shape>, f32) {
+    best_random_local_search(kind, alpha, num_climbs, num_rand, num_workers, evaluator, rng, |shape, error, evaluator, rng| {
+        anneal(shape, error, schedule, evaluator, rng)
+    })
+}
+
+// Runs `num_searches` random searches, then refines each one's best shape by
+// `local_search` and keeps the best result.  The random searches are split
+// into jobs that all share the `num_workers` threads, and then the local
+// searches run in parallel, one per thread.
+#[allow(clippy::too_many_arguments)]
+fn best_random_local_search<R, F>(
+    kind: &ShapeKind, alpha: Alpha,
+    num_searches: u32, num_rand: u32, num_workers: usize,
+    evaluator: &Evaluator, rng: &mut R, local_search: F
+) -> (Box<dyn Shape>, f32)
+where
+    R: Rng,
+    F: Fn(Box<dyn Shape>, f32, &Evaluator, &mut ChaCha8Rng) -> (Box<dyn Shape>, f32) + Sync,
+{
+    // with an evaluation budget, each random search gets at most half of its
+    // search's share so the local search has the rest
+    let (num_searches, num_rand) = match evaluator.remaining() {
+        Some(left) => {
+            let (num_searches, _) = fit_budget(num_searches, num_rand, Some(left));
+            let half_share = (left / num_searches as u64).div_ceil(2);
+            (num_searches, num_rand.min(half_share.min(u32::MAX as u64) as u32))
+        }
+        None => (num_searches, num_rand),
+    };
+    let starts = random_searches(kind, alpha, num_searches, num_rand, num_workers, evaluator, rng);
+    best_of_jobs(starts.len(), num_workers, evaluator, rng, |job, evaluator, rng| {
+        let (init_shape, init_error) = &starts[job];
+        local_search(clone_box(&**init_shape), *init_error, evaluator, rng)
+    })
+}
+
+// Evolves a population of random shapes for `settings.generations`
+// generations.  Each generation keeps the elites and fills the rest with
+// children of tournament winners: same-kind parents are mixed parameter by
+// parameter with chance `crossover_rate`, and every child that isn't a valid
+// crossover is a mutated copy instead.  All random choices happen on this
+// thread and only the scoring is spread across `num_workers` threads, so the
+// result doesn't depend on the number of workers.  An evaluation budget is
+// kept exactly by shrinking the first population or the last generation to
+// fit (though at least one shape is always scored), while time is checked
+// between generations.
+pub fn genetic<R: Rng>(
+    kind: &ShapeKind, alpha: Alpha,
+    settings: &Genetic, num_workers: usize,
+    evaluator: &Evaluator, rng: &mut R
+) -> (Box<dyn Shape>, f32) {
+    let dimensions = evaluator.dimensions();
+    let population_size = settings.population.max(2) as usize;
+    let elites = (settings.elites as usize).min(population_size - 1);
+    let tournament = settings.tournament.max(1) as usize;
+    let rate = StepSize::new(dimensions).rate();
+
+    // never score more shapes than the budget has left
+    let affordable = |count: usize| evaluator.remaining().map_or(count, |left| count.min(left as usize));
+
+    let shapes = (0..affordable(population_size).max(1)).map(|_| kind.random(dimensions, alpha, rng)).collect();
+    let mut population = evaluate_all(shapes, num_workers, evaluator);
+    for _ in 0..settings.generations {
+        let num_children = affordable(population_size - elites);
+        if num_children == 0 || evaluator.is_exhausted() {
+            break;
+        }
+        population.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let mut children = Vec::with_capacity(num_children);
+        while children.len() < num_children {
+            let parent = select(&population, tournament, rng);
+            let other = select(&population, tournament, rng);
+            let crossed = if rng.gen::<f32>() < settings.crossover_rate {
+                crossover(parent, other, rng).filter(|child| child.is_valid(dimensions))
+            } else {
+                None
+            };
+            let child = match crossed {
+                Some(child) => child,
+                None => {
+                    let mut child = clone_box(parent);
+                    child.mutate(dimensions, rate, rng);
+                    child
+                }
+            };
+            children.push(child);
+        }
+        // a generation cut short by the budget keeps the best of the last one
+        // in place of the children it can't score
+        population.truncate(population_size - num_children);
+        population.extend(evaluate_all(children, num_workers, evaluator));
+    }
+    population.into_iter().min_by(|a, b| a.1.total_cmp(&b.1)).expect("population is never empty")
+}
+
+// The best of `tournament` shapes drawn at random from `population`
+fn select<'a, R: Rng>(population: &'a [(Box<dyn Shape>, f32)], tournament: usize, rng: &mut R) -> &'a (dyn Shape + 'static) {
+    let mut best = &population[rng.gen_range(0..population.len())];
+    for _ in 1..tournament {
+        let contender = &population[rng.gen_range(0..population.len())];
+        if contender.1 < best.1 {
+            best = contender;
+        }
+    }
+    &*best.0
+}
+
+// Takes each parameter from either parent, or None if the parents are of
+// different kinds or the mix isn't a valid shape.  The alpha comes from the
+// parent the child is built on.
+fn crossover<R: Rng>(parent: &dyn Shape, other: &dyn Shape, rng: &mut R) -> Option<Box<dyn Shape>> {
+    if parent.kind() != other.kind() {
+        return None;
+    }
+    let (params, other_params) = (parent.params(), other.params());
+    let mixed: Vec<i32> = params.iter().zip(other_params.iter())
+        .map(|(&a, &b)| if rng.gen::<bool>() { a } else { b })
+        .collect();
+    let base = if rng.gen::<bool>() { parent } else { other };
+    base.with_params(&mixed)
+}
+
+// Scores `shapes` across `num_workers` threads, keeping their order
+fn evaluate_all(mut shapes: Vec<Box<dyn Shape>>, num_workers: usize, evaluator: &Evaluator) -> Vec<(Box<dyn Shape>, f32)> {
+    let num_workers = num_workers.min(shapes.len()).max(1);
+    let total = shapes.len() as u32;
+    let mut chunks = Vec::with_capacity(num_workers);
+    for worker in (0..num_workers).rev() {
+        let size = share(total, num_workers, worker) as usize;
+        chunks.push(shapes.split_off(shapes.len() - size));
+    }
+    chunks.reverse();
+    thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || {
+                chunk.into_iter()
+                    .map(|shape| {
+                        let error = evaluator.error(&*shape);
+                        (shape, error)
+                    })
+                    .collect::<Vec<_>>()
+            }))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("optimizer worker panicked"))
+            .collect()
+    })
+}
+
+// Runs `job` for each of `num_jobs` jobs across `num_workers` threads and
+// keeps the best result, with ties going to the lowest job index
+fn best_of_jobs<R, F>(num_jobs: usize, num_workers: usize, evaluator: &Evaluator, rng: &mut R, job: F) -> (Box<dyn Shape>, f32)
+where
+    R: Rng,
+    F: Fn(usize, &Evaluator, &mut ChaCha8Rng) -> (Box<dyn Shape>, f32) + Sync,
+{
+    best(run_jobs(num_jobs, num_workers, evaluator, rng, job))
+}
+
+// Runs `job` for each of `num_jobs` jobs across `num_workers` threads and
+// returns the results in job order.  Every job owns an RNG seeded from `rng`
+// and its share of `evaluator`'s budget, so the results only depend on `rng`
+// and the number of jobs.  They are the same for any number of workers and
+// any thread scheduling, unless the budget includes time.
+fn run_jobs<R, F>(num_jobs: usize, num_workers: usize, evaluator: &Evaluator, rng: &mut R, job: F) -> Vec<(Box<dyn Shape>, f32)>
+where
+    R: Rng,
+    F: Fn(usize, &Evaluator, &mut ChaCha8Rng) -> (Box<dyn Shape>, f32) + Sync,
+{
+    let job = &job;
+    let num_workers = num_workers.min(num_jobs).max(1);
+    let seeds: Vec<u64> = (0..num_jobs).map(|_| rng.gen()).collect();
+    let evaluators: Vec<Evaluator> = (0..num_jobs).map(|index| evaluator.split(num_jobs, index)).collect();
+    let mut jobs: Vec<(usize, u64, &Evaluator)> = seeds.into_iter().zip(evaluators.iter()).enumerate()
+        .map(|(index, (seed, evaluator))| (index, seed, evaluator))
+        .collect();
+    let mut chunks = Vec::with_capacity(num_workers);
+    for worker in (0..num_workers).rev() {
+        let size = share(num_jobs as u32, num_workers, worker) as usize;
+        chunks.push(jobs.split_off(jobs.len() - size));
+    }
+    chunks.reverse();
+    let results: Vec<(Box<dyn Shape>, f32)> = thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || {
+                chunk.into_iter()
+                    .map(|(index, seed, evaluator)| job(index, evaluator, &mut ChaCha8Rng::seed_from_u64(seed)))
+                    .collect::<Vec<_>>()
+            }))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("optimizer worker panicked"))
+            .collect()
+    });
+    let used: u64 = evaluators.iter().map(|job| job.evaluations()).sum();
+    evaluator.evaluations.fetch_add(used, Ordering::Relaxed);
+    results
+}
+
+// The result with the lowest error, the first one on ties
+fn best<I: IntoIterator<Item = (Box<dyn Shape>, f32)>>(results: I) -> (Box<dyn Shape>, f32) {
+    let mut results = results.into_iter();
+    let (mut shape, mut error) = results.next().expect("at least one result");
+    for (new_shape, new_error) in results {
         if new_error < error {
             shape = new_shape;
             error = new_error;
         }
     }
-    println!("best hill climb: {}, {}", shape, error);
     (shape, error)
-}
\ No newline at end of file
+}
+
+// `num_searches` searches of `num_rand` shapes each, cut so they score no more
+// than `left` shapes.  At least one shape is still scored with nothing left.
+fn fit_budget(num_searches: u32, num_rand: u32, left: Option<u64>) -> (u32, u32) {
+    let num_searches = num_searches.max(1);
+    match left {
+        Some(left) => {
+            let left = left.clamp(1, u32::MAX as u64) as u32;
+            let num_searches = num_searches.min(left);
+            (num_searches, num_rand.min(left / num_searches).max(1))
+        }
+        None => (num_searches, num_rand),
+    }
+}
+
+// How much of `total` work the given part does, spreading the remainder over
+// the first parts
+fn share(total: u32, num_parts: usize, part: usize) -> u32 {
+    let num_parts = num_parts as u32;
+    let extra = if (part as u32) < total % num_parts { 1 } else { 0 };
+    total / num_parts + extra
+}
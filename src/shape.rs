@@ -1,81 +1,324 @@
 use std::fmt::{Debug, Display};
+use std::str::FromStr;
 
 use image::{Rgb, RgbImage};
-use rand::{Rng, rngs::ThreadRng};
+use rand::{Rng, RngCore};
+use rand::seq::SliceRandom;
+use rand::distributions::Uniform;
+use rand_distr::{StandardNormal, Distribution};
 use dyn_clone::DynClone;
 
 use crate::error;
-use crate::util::{clamp};
+use crate::metric::Metric;
+use crate::weights::Weights;
+use crate::util::{clamp, blend, best_color_in_rows, partial_square_error};
 
+// Implements Mutatable, Drawable and Rasterizable for a shape struct, which
+// keeps its `alpha` and its rows in fields of those names and perturbs itself
+// with a `mutate_once` method that `mutate_valid` retries
+macro_rules! impl_shape_traits {
+    ($shape:ty) => {
+        impl $crate::shape::Mutatable for $shape {
+            fn mutate(&mut self, dimensions: (u32, u32), rate: f32, rng: &mut dyn rand::RngCore) {
+                $crate::shape::mutate_valid(self, dimensions, |shape| shape.mutate_once(dimensions, rate, rng));
+            }
+        }
+
+        impl $crate::shape::Drawable for $shape {
+            fn alpha(&self) -> u8 {
+                self.alpha.value()
+            }
+        }
+
+        impl $crate::shape::Rasterizable for $shape {
+            fn rasterize(&self) -> &[$crate::shape::Row] {
+                self.raster.as_deref().expect("raster was not created")
+            }
+        }
+    };
+}
+
+mod circle;
 mod ellipse;
+mod polygon;
+mod quadratic_bezier;
+mod rectangle;
+mod rotated_ellipse;
+mod rotated_rectangle;
 mod row;
+mod triangle;
 
+pub use circle::Circle;
 pub use ellipse::Ellipse;
+pub use polygon::Polygon;
+pub use quadratic_bezier::QuadraticBezier;
+pub use rectangle::Rectangle;
+pub use rotated_ellipse::RotatedEllipse;
+pub use rotated_rectangle::RotatedRectangle;
 pub use row::Row;
+pub use triangle::Triangle;
+
+// Fewest pixels of the canvas a valid shape covers
+pub const MIN_AREA: u32 = 4;
+
+// How many times a random shape or a mutation is retried when it comes out
+// invalid
+const MAX_ATTEMPTS: u32 = 100;
 
-// currently, only Ellipse shape.  Maybe add rotated ellipse, polygon when done?
+#[derive(Debug, Clone, PartialEq)]
 pub enum ShapeKind {
     Ellipse,
+    RotatedEllipse,
+    Triangle,
+    Rectangle,
+    RotatedRectangle,
+    QuadraticBezier,
+    Polygon { sides: usize },
+    Circle,
+    // picks one of the listed kinds at random for every candidate
+    Any(Vec<ShapeKind>),
 }
 
 impl ShapeKind {
-    pub fn random(&self, dimensions: (u32, u32), mut rng: &mut ThreadRng) -> Box<dyn Shape> {
+    // A random valid shape of this kind.  Gives up after MAX_ATTEMPTS and
+    // returns the last try, which only happens on canvases too small for the
+    // kind; such a shape covers too little to change the error much.
+    pub fn random<R: Rng + ?Sized>(&self, dimensions: (u32, u32), alpha: Alpha, rng: &mut R) -> Box<dyn Shape> {
+        let mut shape = self.random_once(dimensions, alpha, rng);
+        for _ in 1..MAX_ATTEMPTS {
+            if shape.is_valid(dimensions) {
+                break;
+            }
+            shape = self.random_once(dimensions, alpha, rng);
+        }
+        shape
+    }
+
+    // Whether shapes of this kind can be made at all: polygons need at least
+    // 3 sides and Any needs at least one kind
+    pub fn validate(&self) -> error::Result<()> {
         match self {
-            Self::Ellipse => Box::new(Ellipse::random(dimensions, &mut rng))
+            Self::Polygon { sides } if *sides < 3 => {
+                Err(error::Error::ArgumentError(format!("a polygon needs at least 3 sides, got {}", sides)))
+            }
+            Self::Any(kinds) if kinds.is_empty() => {
+                Err(error::Error::ArgumentError("a mix of shape kinds needs at least one kind".to_string()))
+            }
+            Self::Any(kinds) => kinds.iter().try_for_each(|kind| kind.validate()),
+            _ => Ok(()),
+        }
+    }
+
+    fn random_once<R: Rng + ?Sized>(&self, dimensions: (u32, u32), alpha: Alpha, rng: &mut R) -> Box<dyn Shape> {
+        match self {
+            Self::Ellipse => Box::new(Ellipse::random(dimensions, alpha, rng)),
+            Self::RotatedEllipse => Box::new(RotatedEllipse::random(dimensions, alpha, rng)),
+            Self::Triangle => Box::new(Triangle::random(dimensions, alpha, rng)),
+            Self::Rectangle => Box::new(Rectangle::random(dimensions, alpha, rng)),
+            Self::RotatedRectangle => Box::new(RotatedRectangle::random(dimensions, alpha, rng)),
+            Self::QuadraticBezier => Box::new(QuadraticBezier::random(dimensions, alpha, rng)),
+            Self::Polygon { sides } => Box::new(Polygon::random(*sides, dimensions, alpha, rng)),
+            Self::Circle => Box::new(Circle::random(dimensions, alpha, rng)),
+            Self::Any(kinds) => kinds
+                .choose(rng)
+                .expect("ShapeKind::Any needs at least one kind")
+                .random(dimensions, alpha, rng),
         }
     }
 }
 
+// Parses names like "ellipse", "polygon-5", or a comma separated list such as
+// "ellipse,triangle" for ShapeKind::Any
+impl FromStr for ShapeKind {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> error::Result<Self> {
+        if s.contains(',') {
+            let kinds = s.split(',').map(|kind| kind.trim().parse()).collect::<error::Result<Vec<_>>>()?;
+            return Ok(ShapeKind::Any(kinds));
+        }
+        let kind = match s {
+            "ellipse" => ShapeKind::Ellipse,
+            "rotated-ellipse" => ShapeKind::RotatedEllipse,
+            "circle" => ShapeKind::Circle,
+            "triangle" => ShapeKind::Triangle,
+            "rectangle" => ShapeKind::Rectangle,
+            "rotated-rectangle" => ShapeKind::RotatedRectangle,
+            "bezier" => ShapeKind::QuadraticBezier,
+            _ => {
+                let sides = s.strip_prefix("polygon-")
+                    .and_then(|sides| sides.parse::<usize>().ok())
+                    .ok_or_else(|| error::Error::ArgumentError(format!("unknown shape kind '{}'", s)))?;
+                ShapeKind::Polygon { sides }
+            }
+        };
+        kind.validate()?;
+        Ok(kind)
+    }
+}
+
+// Opacity of a shape.  A fixed alpha never changes, while an automatic alpha
+// starts at the given value and is mutated along with the shape's geometry,
+// letting the error function choose the opacity too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alpha {
+    Fixed(u8),
+    Auto(u8),
+}
+
+impl Alpha {
+    // range an automatic alpha is kept within; too transparent shapes barely
+    // change the image and make best_color_in_rows unstable
+    pub const AUTO_MIN: u8 = 16;
+    pub const AUTO_MAX: u8 = 255;
+
+    pub fn auto() -> Self {
+        Alpha::Auto(128)
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            Alpha::Fixed(alpha) | Alpha::Auto(alpha) => *alpha,
+        }
+    }
+
+    // Perturbs an automatic alpha with the same probability as each of the
+    // shape's `num_params` geometric parameters.  Returns whether it did, so
+    // the caller knows to leave its geometry alone.
+    pub fn mutate<R: Rng + ?Sized>(&mut self, num_params: u32, rng: &mut R) -> bool {
+        let rate = 16.0;
+        match self {
+            Alpha::Fixed(_) => false,
+            Alpha::Auto(alpha) => {
+                if Uniform::new(0, num_params + 1).sample(rng) != 0 {
+                    return false;
+                }
+                let delta = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+                *alpha = clamp(*alpha as i32 + delta, Self::AUTO_MIN as i32, Self::AUTO_MAX as i32) as u8;
+                true
+            }
+        }
+    }
+}
+
+// Parses "auto" or a fixed alpha from 1 to 255
+impl FromStr for Alpha {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> error::Result<Self> {
+        if s == "auto" {
+            return Ok(Alpha::auto());
+        }
+        match s.parse::<u8>() {
+            Ok(alpha) if alpha > 0 => Ok(Alpha::Fixed(alpha)),
+            _ => Err(error::Error::ArgumentError(format!("alpha must be 1-255 or 'auto', got '{}'", s))),
+        }
+    }
+}
+
+impl Default for Alpha {
+    fn default() -> Self {
+        Alpha::Fixed(128)
+    }
+}
+
 // TRAITS
-pub trait Shape: Mutatable + Drawable + DynClone + Display {
-    fn error(&self, source: &RgbImage, target: &RgbImage) -> f32;
+pub trait Shape: Mutatable + Drawable + DynClone + Display + Send + Sync {
+    // Error against `target` under `metric` and `weights` after drawing this
+    // shape over `source`, where `error` is the current error between the two.
+    // Only the pixels covered by the shape are visited, and neither image is
+    // copied.
+    fn error(&self, source: &RgbImage, target: &RgbImage, error: f32, metric: Metric, weights: Option<&Weights>) -> f32 {
+        let rows = self.rasterize();
+        let color = best_color_in_rows(rows, self.alpha(), source, target, metric, weights);
+        partial_square_error(error, rows, color, self.alpha(), source, target, metric, weights)
+    }
+    fn kind(&self) -> ShapeKind;
+    // Whether the shape covers at least MIN_AREA pixels of a canvas of size
+    // `dimensions`, or a quarter of a canvas too small for that.  Shapes off
+    // the canvas or too thin to cover a pixel would otherwise be scored with
+    // no pixels to pick a color from.
+    fn is_valid(&self, dimensions: (u32, u32)) -> bool {
+        has_min_area(self.rasterize(), dimensions)
+    }
+    // A copy with its geometry scaled by `scale`, for rendering at another
+    // resolution than the one it was optimized at
+    fn scaled(&self, scale: f32) -> Box<dyn Shape>;
+    // The geometry as a flat list, the same length for every shape of a kind
+    fn params(&self) -> Vec<i32>;
+    // A shape of the same kind and alpha with the given geometry, or None if
+    // it isn't a valid shape
+    fn with_params(&self, params: &[i32]) -> Option<Box<dyn Shape>>;
+    // An svg element for the shape drawn in `color`.  Raster rows cover whole
+    // pixels, so pixel (x, y) spans (x, y) to (x + 1, y + 1) in svg units.
+    fn svg(&self, color: Rgb<u8>, alpha: u8) -> String;
+}
+
+// Scales a pixel index so that pixel centers stay aligned, e.g. doubling maps
+// pixel 0 to pixel 0.5 (rounded to 1) and pixel 1 to pixel 2.5.
+pub fn scale_index(index: i32, scale: f32) -> i32 {
+    ((index as f32 + 0.5) * scale - 0.5).round() as i32
+}
+
+// Scales a length or a continuous coordinate, where pixel centers already lie
+// halfway between integers
+pub fn scale_length(length: i32, scale: f32) -> i32 {
+    (length as f32 * scale).round() as i32
+}
+
+pub fn has_min_area(rows: &[Row], (width, height): (u32, u32)) -> bool {
+    let area: u32 = rows.iter()
+        .filter_map(|row| row.clip(width, height))
+        .map(|(x1, x2, _)| x2 - x1 + 1)
+        .sum();
+    area >= MIN_AREA.min((width * height).div_ceil(4))
+}
+
+// Applies `mutation` to `shape`, starting over from the original whenever the
+// result isn't valid.  Leaves the shape as it was if no attempt is.
+fn mutate_valid<S: Shape + Clone, F: FnMut(&mut S)>(shape: &mut S, dimensions: (u32, u32), mut mutation: F) {
+    let original = shape.clone();
+    for _ in 0..MAX_ATTEMPTS {
+        mutation(shape);
+        if shape.is_valid(dimensions) {
+            return;
+        }
+        *shape = original.clone();
+    }
 }
 
 pub trait Mutatable {
-    fn mutate(&mut self, dimensions: (u32, u32), rng: &mut ThreadRng);
+    // Randomly perturbs the shape, moving coordinates by a normal step with a
+    // standard deviation of `rate` pixels.  The shape stays valid.
+    fn mutate(&mut self, dimensions: (u32, u32), rate: f32, rng: &mut dyn RngCore);
 }
 
 pub trait Drawable: Rasterizable {
-    fn best_color(&self, source: &RgbImage, target: &RgbImage) -> Rgb<u8>;
-    fn draw_best_color(&self, source: &mut RgbImage, target: &RgbImage);
+    fn alpha(&self) -> u8;
+    fn best_color(&self, source: &RgbImage, target: &RgbImage, metric: Metric, weights: Option<&Weights>) -> Rgb<u8> {
+        best_color_in_rows(self.rasterize(), self.alpha(), source, target, metric, weights)
+    }
+    fn draw_best_color(&self, source: &mut RgbImage, target: &RgbImage, metric: Metric, weights: Option<&Weights>) -> Rgb<u8> {
+        let best_color = self.best_color(source, target, metric, weights);
+        self.draw_to_image(source, best_color, self.alpha());
+        best_color
+    }
     fn draw_to_image(&self, img: &mut RgbImage, color: Rgb<u8>, alpha: u8) {
-        let rows = self.rasterize();
-        let alpha: f32 = alpha as f32 / 255.0;
         let (width, height) = img.dimensions();
-        let (width, height) = (width as i32, height as i32);
-        for row in rows {
-            let (x1, x2, y) = row.into();
-            if y < 0 || y >= height {
+        for row in self.rasterize() {
+            let Some((x1, x2, y)) = row.clip(width, height) else {
                 continue;
-            }
-            let (x1, x2) = (clamp(x1, 0, width-1) as u32, clamp(x2, 0, width-1) as u32);
-            let y = y as u32;
+            };
             for x in x1..x2+1 {
-                let pixel = img.get_pixel(x, y);
-                let [img_r, img_g, img_b] = pixel.0;
-                let [r, g, b] = color.0;
-
-                let [new_r, new_g, new_b] = [
-                    (r as f32 * alpha + img_r as f32 * (1.0  - alpha)).round() as i32,
-                    (g as f32 * alpha + img_g as f32 * (1.0 - alpha)).round() as i32,
-                    (b as f32 * alpha + img_b as f32 * (1.0 - alpha)).round() as i32
-                    ];
-                // dbg!(new_r);
-                let [new_r, new_g, new_b] = [
-                    clamp(new_r, 0, 255) as u8,
-                    clamp(new_g, 0, 255) as u8,
-                    clamp(new_b, 0, 255) as u8
-                    ];
-                // dbg!(new_r, new_g, new_b);
-                img.put_pixel(x, y, Rgb([new_r, new_g, new_b]));
+                let pixel = *img.get_pixel(x, y);
+                img.put_pixel(x, y, blend(pixel, color, alpha));
             }
         }
     }
 }
 
 pub trait Rasterizable {
-    fn rasterize(&self) -> Vec<Row>;
-    fn new_raster(&self) -> Vec<Row>;
+    fn rasterize(&self) -> &[Row];
 }
 
 // STRUCTS
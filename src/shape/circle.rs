@@ -0,0 +1,93 @@
+use std::fmt;
+
+use image::Rgb;
+
+use rand::{Rng, RngCore};
+use rand::distributions::Uniform;
+use rand_distr::{StandardNormal, Distribution};
+
+use crate::shape::{Shape, ShapeKind, Alpha};
+use crate::shape::{Row, scale_index};
+use crate::shape::ellipse::ellipse_rows;
+use crate::util::{clamp};
+use crate::svg;
+
+#[derive(Debug, Default, Clone)]
+pub struct Circle {
+    raster: Option<Vec<Row>>,
+    x: i32,
+    y: i32,
+    radius: i32,
+    alpha: Alpha,
+}
+
+impl Circle {
+    pub fn new(x: i32, y: i32, radius: i32, alpha: Alpha) -> Self {
+        let mut circle = Circle {raster: None, x, y, radius, alpha};
+        circle.raster = Some(circle.new_raster());
+        circle
+    }
+    pub fn random<R: Rng + ?Sized>(dimensions: (u32, u32), alpha: Alpha, rng: &mut R) -> Self {
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let (x_distr, y_distr) = (Uniform::new(0, width), Uniform::new(0, height));
+        let x = x_distr.sample(rng);
+        let y = y_distr.sample(rng);
+        let radius = Uniform::new_inclusive(1, (width.min(height) - 1).max(1)).sample(rng);
+        Circle::new(x, y, radius, alpha)
+    }
+}
+
+impl Shape for Circle {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::Circle
+    }
+
+    fn scaled(&self, scale: f32) -> Box<dyn Shape> {
+        Box::new(Circle::new(scale_index(self.x, scale), scale_index(self.y, scale), scale_index(self.radius, scale), self.alpha))
+    }
+
+    fn params(&self) -> Vec<i32> {
+        vec![self.x, self.y, self.radius]
+    }
+
+    fn with_params(&self, params: &[i32]) -> Option<Box<dyn Shape>> {
+        match *params {
+            [x, y, radius] => Some(Box::new(Circle::new(x, y, radius, self.alpha))),
+            _ => None,
+        }
+    }
+
+    fn svg(&self, color: Rgb<u8>, alpha: u8) -> String {
+        format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {} />",
+            self.x as f32 + 0.5, self.y as f32 + 0.5, self.radius as f32 + 0.5, svg::fill(color, alpha))
+    }
+}
+
+impl_shape_traits!(Circle);
+
+impl Circle {
+    fn mutate_once(&mut self, dimensions: (u32, u32), rate: f32, rng: &mut dyn RngCore) {
+        if self.alpha.mutate(3, rng) {
+            return;
+        }
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let delta = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        match Uniform::new(0, 3).sample(rng) {
+            0 => self.x = clamp(self.x + delta, 0, width-1),
+            1 => self.y = clamp(self.y + delta, 0, height-1),
+            _ => self.radius = clamp(self.radius + delta, 1, (width.max(height)-1).max(1)),
+        }
+        self.raster = Some(self.new_raster());
+    }
+
+    fn new_raster(&self) -> Vec<Row> {
+        ellipse_rows(self.x, self.y, self.radius, self.radius)
+    }
+}
+
+// For printing Circle information!
+impl fmt::Display for Circle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Circle centered at ({}, {}) with radius {}", self.x, self.y, self.radius)
+    }
+}
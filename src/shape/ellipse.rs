@@ -1,16 +1,15 @@
 use std::fmt;
 
-use image::{Rgb, RgbImage};
+use image::Rgb;
 
-use rand::Rng;
-use rand::rngs::ThreadRng;
+use rand::{Rng, RngCore};
 use rand::distributions::Uniform;
 use rand_distr::{StandardNormal, Distribution};
 
-use crate::shape::{Shape, Rasterizable, Drawable, Mutatable};
-use crate::shape::{Row};
-use crate::util::{clamp, best_color_in_rows, mean_square_error};
-use crate::error;
+use crate::shape::{Shape, ShapeKind, Alpha};
+use crate::shape::{Row, scale_index};
+use crate::util::{clamp};
+use crate::svg;
 
 #[derive(Debug, Default, Clone)]
 pub struct Ellipse {
@@ -19,40 +18,59 @@ pub struct Ellipse {
     y: i32,
     x_radius: i32,
     y_radius: i32,
-    alpha: u8,
+    alpha: Alpha,
 }
 
 impl Ellipse {
-    pub fn new(x: i32, y: i32, x_radius: i32, y_radius: i32, alpha: u8) -> Self {
+    pub fn new(x: i32, y: i32, x_radius: i32, y_radius: i32, alpha: Alpha) -> Self {
         let mut ellipse = Ellipse {raster: None, x, y, x_radius, y_radius, alpha};
         ellipse.raster = Some(ellipse.new_raster());
         ellipse
     }
-    pub fn random(dimensions: (u32, u32), mut rng: &mut ThreadRng) -> Self {
+    pub fn random<R: Rng + ?Sized>(dimensions: (u32, u32), alpha: Alpha, mut rng: &mut R) -> Self {
         let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
         let (x_distr, y_distr) = (Uniform::new(0, width), Uniform::new(0, height));
         let x = x_distr.sample(&mut rng);
         let y = y_distr.sample(&mut rng);
-        let x_radius = x_distr.sample(&mut rng);
-        let y_radius = y_distr.sample(&mut rng);
-        Ellipse::new(x, y, x_radius, y_radius, 128)
+        let x_radius = Uniform::new_inclusive(1, (width - 1).max(1)).sample(&mut rng);
+        let y_radius = Uniform::new_inclusive(1, (height - 1).max(1)).sample(&mut rng);
+        Ellipse::new(x, y, x_radius, y_radius, alpha)
     }
 }
 
 impl Shape for Ellipse {
-    fn error(&self, source: &RgbImage, target: &RgbImage) -> f32 {
-        
-        let mut source = source.clone();
-        self.draw_best_color(&mut source, &target);
-        // let best_color = best_color_in_rows(&self.rasterize(), self.alpha, &source, &target);
-        // self.draw_to_image(&mut source, best_color, self.alpha);
-        mean_square_error(&source, &target)
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::Ellipse
+    }
+
+    fn scaled(&self, scale: f32) -> Box<dyn Shape> {
+        Box::new(Ellipse::new(scale_index(self.x, scale), scale_index(self.y, scale), scale_index(self.x_radius, scale), scale_index(self.y_radius, scale), self.alpha))
+    }
+
+    fn params(&self) -> Vec<i32> {
+        vec![self.x, self.y, self.x_radius, self.y_radius]
+    }
+
+    fn with_params(&self, params: &[i32]) -> Option<Box<dyn Shape>> {
+        match *params {
+            [x, y, x_radius, y_radius] => Some(Box::new(Ellipse::new(x, y, x_radius, y_radius, self.alpha))),
+            _ => None,
+        }
+    }
+
+    fn svg(&self, color: Rgb<u8>, alpha: u8) -> String {
+        format!("<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" {} />",
+            self.x as f32 + 0.5, self.y as f32 + 0.5, self.x_radius as f32 + 0.5, self.y_radius as f32 + 0.5, svg::fill(color, alpha))
     }
 }
 
-impl Mutatable for Ellipse {
-    fn mutate(&mut self, dimensions: (u32, u32), mut rng: &mut ThreadRng) {
-        let rate = 4.0;
+impl_shape_traits!(Ellipse);
+
+impl Ellipse {
+    fn mutate_once(&mut self, dimensions: (u32, u32), rate: f32, mut rng: &mut dyn RngCore) {
+        if self.alpha.mutate(4, rng) {
+            return;
+        }
         let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
         match Uniform::new(0, 4).sample(&mut rng) {
             0 => {
@@ -74,14 +92,14 @@ impl Mutatable for Ellipse {
                 let delta = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
                 // println!("delta: {}", delta);
                 let new_x_radius = self.x_radius + delta;
-                self.x_radius = clamp(new_x_radius, 0, width-1);
+                self.x_radius = clamp(new_x_radius, 1, (width-1).max(1));
             },
             3 => {
                 // println!("y_rad mutate");
                 let delta = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
                 // println!("delta: {}", delta);
                 let new_y_radius = self.y_radius + delta;
-                self.y_radius = clamp(new_y_radius, 0, height-1);
+                self.y_radius = clamp(new_y_radius, 1, (height-1).max(1));
             },
             _ => {
                 println!("do nothing lmao");
@@ -90,45 +108,36 @@ impl Mutatable for Ellipse {
         }
         self.raster = Some(self.new_raster());
     }
-}
 
-impl Drawable for Ellipse {
-    fn best_color(&self, source: &RgbImage, target: &RgbImage) -> Rgb<u8> {
-        best_color_in_rows(&self.rasterize(), self.alpha, &source, &target)
-    }
-
-    fn draw_best_color(&self, mut source: &mut RgbImage, target: &RgbImage) {
-        let best_color = self.best_color(&source, &target);
-        self.draw_to_image(&mut source, best_color, self.alpha);
+    fn new_raster(&self) -> Vec<Row> {
+        ellipse_rows(self.x, self.y, self.x_radius, self.y_radius)
     }
 }
 
-impl Rasterizable for Ellipse {
-    fn rasterize(&self) -> Vec<Row> {
-        self.raster.as_ref().expect("raster was not created").to_vec()
-    }
-
-    fn new_raster(&self) -> Vec<Row> {
-        let mut rows = Vec::new();
-        let (a, b) = (self.x_radius, self.y_radius);
-        let (x_c, y_c) = (self.x, self.y);
-        let b2: i32 = b.pow(2);
-        let ratio = a as f64 / b as f64;
+// Scanline rows of an axis-aligned ellipse, shared with Circle
+pub(super) fn ellipse_rows(x_c: i32, y_c: i32, a: i32, b: i32) -> Vec<Row> {
+    let mut rows = Vec::new();
+    // a flat ellipse is just its middle row, and would divide by zero below
+    if b <= 0 {
         rows.push(Row::new(x_c - a, x_c + a, y_c));
-        let mut y: i32 = 1;
-        while y < b {
-            let x = (((b2 - y*y) as f64).sqrt() * ratio).round() as i32;
-            let (x1, x2) = (x_c - x, x_c + x);
-            let (y1, y2) = (y_c - y, y_c + y);
-            if y_c >= y {
-                rows.push(Row::new(x1, x2, y1));
-            }
-            rows.push(Row::new(x1, x2, y2));
-            y += 1;
+        return rows;
+    }
+    let b2: i32 = b.pow(2);
+    let ratio = a as f64 / b as f64;
+    rows.push(Row::new(x_c - a, x_c + a, y_c));
+    let mut y: i32 = 1;
+    while y < b {
+        let x = (((b2 - y*y) as f64).sqrt() * ratio).round() as i32;
+        let (x1, x2) = (x_c - x, x_c + x);
+        let (y1, y2) = (y_c - y, y_c + y);
+        if y_c >= y {
+            rows.push(Row::new(x1, x2, y1));
         }
-        // rows.sort();
-        rows
+        rows.push(Row::new(x1, x2, y2));
+        y += 1;
     }
+    // rows.sort();
+    rows
 }
 
 // For printing Ellipse information!
@@ -0,0 +1,205 @@
+use std::fmt;
+
+use image::Rgb;
+
+use rand::{Rng, RngCore};
+use rand::distributions::Uniform;
+use rand_distr::{StandardNormal, Distribution};
+
+use crate::shape::{Shape, ShapeKind, Alpha, Rasterizable, has_min_area};
+use crate::shape::{Row, scale_length};
+use crate::util::{clamp};
+use crate::svg;
+
+#[derive(Debug, Default, Clone)]
+pub struct Polygon {
+    raster: Option<Vec<Row>>,
+    vertices: Vec<(i32, i32)>,
+    alpha: Alpha,
+}
+
+impl Polygon {
+    // Fewer than 3 vertices give a polygon that covers nothing and isn't valid
+    pub fn new(vertices: Vec<(i32, i32)>, alpha: Alpha) -> Self {
+        let mut polygon = Polygon {raster: None, vertices, alpha};
+        polygon.raster = Some(polygon.new_raster());
+        polygon
+    }
+    // Random points sorted by angle around their centroid form a simple
+    // (star-shaped) polygon unless points coincide or line up, which
+    // ShapeKind::random retries like any other invalid shape.
+    pub fn random<R: Rng + ?Sized>(sides: usize, dimensions: (u32, u32), alpha: Alpha, rng: &mut R) -> Self {
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let (x_distr, y_distr) = (Uniform::new(0, width), Uniform::new(0, height));
+        let mut vertices: Vec<(i32, i32)> = (0..sides)
+            .map(|_| (x_distr.sample(rng), y_distr.sample(rng)))
+            .collect();
+        let (sum_x, sum_y) = vertices.iter().fold((0.0, 0.0), |(sx, sy), &(x, y)| (sx + x as f64, sy + y as f64));
+        let (x_c, y_c) = (sum_x / sides as f64, sum_y / sides as f64);
+        let angle = |&(x, y): &(i32, i32)| (y as f64 - y_c).atan2(x as f64 - x_c);
+        vertices.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).expect("NaN vertex angle"));
+        Polygon::new(vertices, alpha)
+    }
+}
+
+impl Shape for Polygon {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::Polygon { sides: self.vertices.len() }
+    }
+
+    fn scaled(&self, scale: f32) -> Box<dyn Shape> {
+        let vertices = self.vertices.iter()
+            .map(|&(x, y)| (scale_length(x, scale), scale_length(y, scale)))
+            .collect();
+        Box::new(Polygon::new(vertices, self.alpha))
+    }
+
+    fn is_valid(&self, dimensions: (u32, u32)) -> bool {
+        is_simple(&self.vertices) && has_min_area(self.rasterize(), dimensions)
+    }
+
+    fn params(&self) -> Vec<i32> {
+        self.vertices.iter().flat_map(|&(x, y)| [x, y]).collect()
+    }
+
+    // Mixed vertices can cross, which gives no shape
+    fn with_params(&self, params: &[i32]) -> Option<Box<dyn Shape>> {
+        if params.len() != 2 * self.vertices.len() {
+            return None;
+        }
+        let vertices: Vec<(i32, i32)> = params.chunks(2).map(|point| (point[0], point[1])).collect();
+        if !is_simple(&vertices) {
+            return None;
+        }
+        Some(Box::new(Polygon::new(vertices, self.alpha)))
+    }
+
+    fn svg(&self, color: Rgb<u8>, alpha: u8) -> String {
+        // the vertices are already in continuous coordinates, see Row::polygon
+        let points: Vec<String> = self.vertices.iter()
+            .map(|&(x, y)| format!("{},{}", x, y))
+            .collect();
+        format!("<polygon points=\"{}\" {} />", points.join(" "), svg::fill(color, alpha))
+    }
+}
+
+impl_shape_traits!(Polygon);
+
+impl Polygon {
+    // Moves a single vertex.  Moves that make the polygon self-intersecting
+    // or degenerate fail `is_valid`, so `mutate_valid` retries them.
+    fn mutate_once(&mut self, dimensions: (u32, u32), rate: f32, rng: &mut dyn RngCore) {
+        if self.alpha.mutate(self.vertices.len() as u32, rng) {
+            return;
+        }
+        if self.vertices.is_empty() {
+            return;
+        }
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let i = Uniform::new(0, self.vertices.len()).sample(rng);
+        let (x, y) = self.vertices[i];
+        let dx = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        let dy = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        self.vertices[i] = (clamp(x + dx, 0, width-1), clamp(y + dy, 0, height-1));
+        self.raster = Some(self.new_raster());
+    }
+
+    fn new_raster(&self) -> Vec<Row> {
+        let points: Vec<(f64, f64)> = self.vertices.iter().map(|&(x, y)| (x as f64, y as f64)).collect();
+        Row::polygon(&points)
+    }
+}
+
+// A polygon is simple when it encloses some area and no two of its edges cross
+// or touch, other than neighbouring edges at their shared vertex.
+fn is_simple(vertices: &[(i32, i32)]) -> bool {
+    let n = vertices.len();
+    if n < 3 {
+        return false;
+    }
+    let doubled_area: i64 = (0..n)
+        .map(|i| cross(vertices[i], vertices[(i + 1) % n], (0, 0)))
+        .sum();
+    if doubled_area == 0 {
+        return false;
+    }
+    for i in 0..n {
+        let (a1, a2) = (vertices[i], vertices[(i + 1) % n]);
+        if a1 == a2 {
+            return false;
+        }
+        for j in i+1..n {
+            // neighbouring edges share a vertex, so skip them
+            if j == i + 1 || (i == 0 && j == n - 1) {
+                continue;
+            }
+            let (b1, b2) = (vertices[j], vertices[(j + 1) % n]);
+            if segments_intersect(a1, a2, b1, b2) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// z component of (a - o) x (b - o)
+fn cross(a: (i32, i32), b: (i32, i32), o: (i32, i32)) -> i64 {
+    let (ax, ay) = ((a.0 - o.0) as i64, (a.1 - o.1) as i64);
+    let (bx, by) = ((b.0 - o.0) as i64, (b.1 - o.1) as i64);
+    ax * by - ay * bx
+}
+
+fn on_segment(p: (i32, i32), a: (i32, i32), b: (i32, i32)) -> bool {
+    p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1)
+}
+
+fn segments_intersect(a1: (i32, i32), a2: (i32, i32), b1: (i32, i32), b2: (i32, i32)) -> bool {
+    let d1 = cross(a2, b1, a1).signum();
+    let d2 = cross(a2, b2, a1).signum();
+    let d3 = cross(b2, a1, b1).signum();
+    let d4 = cross(b2, a2, b1).signum();
+    if d1 * d2 < 0 && d3 * d4 < 0 {
+        return true;
+    }
+    (d1 == 0 && on_segment(b1, a1, a2))
+        || (d2 == 0 && on_segment(b2, a1, a2))
+        || (d3 == 0 && on_segment(a1, b1, b2))
+        || (d4 == 0 && on_segment(a2, b1, b2))
+}
+
+// For printing Polygon information!
+impl fmt::Display for Polygon {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Polygon with vertices")?;
+        for (x, y) in &self.vertices {
+            write!(f, " ({}, {})", x, y)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convex_and_concave_quadrilaterals_are_simple() {
+        assert!(is_simple(&[(0, 0), (10, 0), (10, 10), (0, 10)]));
+        assert!(is_simple(&[(0, 0), (10, 0), (3, 3), (0, 10)]));
+    }
+
+    #[test]
+    fn crossing_quadrilateral_is_not_simple() {
+        // a bowtie, whose first and third edges cross
+        assert!(!is_simple(&[(0, 0), (10, 10), (10, 0), (0, 10)]));
+    }
+
+    #[test]
+    fn touching_or_degenerate_polygons_are_not_simple() {
+        // the fourth vertex lies on the first edge
+        assert!(!is_simple(&[(0, 0), (10, 0), (5, 10), (5, 0)]));
+        assert!(!is_simple(&[(0, 0), (5, 0), (10, 0), (15, 0)]));
+        assert!(!is_simple(&[(0, 0), (10, 0), (10, 0), (0, 10)]));
+        assert!(!is_simple(&[(0, 0), (10, 10)]));
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+
+use image::Rgb;
+
+use rand::{Rng, RngCore};
+use rand::distributions::Uniform;
+use rand_distr::{StandardNormal, Distribution};
+
+use crate::shape::{Shape, ShapeKind, Alpha};
+use crate::shape::{Row, scale_index, scale_length};
+use crate::util::{clamp};
+use crate::svg;
+
+const MAX_RANDOM_WIDTH: i32 = 8;
+
+// A stroked quadratic Bézier curve from `start` to `end`, bent towards `control`
+#[derive(Debug, Default, Clone)]
+pub struct QuadraticBezier {
+    raster: Option<Vec<Row>>,
+    start: (i32, i32),
+    control: (i32, i32),
+    end: (i32, i32),
+    width: i32,
+    alpha: Alpha,
+}
+
+impl QuadraticBezier {
+    pub fn new(start: (i32, i32), control: (i32, i32), end: (i32, i32), width: i32, alpha: Alpha) -> Self {
+        let mut curve = QuadraticBezier {raster: None, start, control, end, width, alpha};
+        curve.raster = Some(curve.new_raster());
+        curve
+    }
+    pub fn random<R: Rng + ?Sized>(dimensions: (u32, u32), alpha: Alpha, rng: &mut R) -> Self {
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let (x_distr, y_distr) = (Uniform::new(0, width), Uniform::new(0, height));
+        let start = (x_distr.sample(rng), y_distr.sample(rng));
+        let control = (x_distr.sample(rng), y_distr.sample(rng));
+        let end = (x_distr.sample(rng), y_distr.sample(rng));
+        let stroke_width = Uniform::new_inclusive(1, MAX_RANDOM_WIDTH).sample(rng);
+        QuadraticBezier::new(start, control, end, stroke_width, alpha)
+    }
+
+    fn point_at(&self, t: f64) -> (f64, f64) {
+        let (x0, y0) = (self.start.0 as f64, self.start.1 as f64);
+        let (x1, y1) = (self.control.0 as f64, self.control.1 as f64);
+        let (x2, y2) = (self.end.0 as f64, self.end.1 as f64);
+        let s = 1.0 - t;
+        (
+            s * s * x0 + 2.0 * s * t * x1 + t * t * x2,
+            s * s * y0 + 2.0 * s * t * y1 + t * t * y2,
+        )
+    }
+}
+
+impl Shape for QuadraticBezier {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::QuadraticBezier
+    }
+
+    fn scaled(&self, scale: f32) -> Box<dyn Shape> {
+        let point = |(x, y): (i32, i32)| (scale_index(x, scale), scale_index(y, scale));
+        let width = scale_length(self.width, scale).max(1);
+        Box::new(QuadraticBezier::new(point(self.start), point(self.control), point(self.end), width, self.alpha))
+    }
+
+    fn params(&self) -> Vec<i32> {
+        vec![self.start.0, self.start.1, self.control.0, self.control.1, self.end.0, self.end.1, self.width]
+    }
+
+    fn with_params(&self, params: &[i32]) -> Option<Box<dyn Shape>> {
+        match *params {
+            [x1, y1, x2, y2, x3, y3, width] => Some(Box::new(QuadraticBezier::new((x1, y1), (x2, y2), (x3, y3), width, self.alpha))),
+            _ => None,
+        }
+    }
+
+    fn svg(&self, color: Rgb<u8>, alpha: u8) -> String {
+        let point = |(x, y): (i32, i32)| format!("{} {}", x as f32 + 0.5, y as f32 + 0.5);
+        format!("<path d=\"M {} Q {} {}\" {} />",
+            point(self.start), point(self.control), point(self.end), svg::stroke(color, alpha, self.width))
+    }
+}
+
+impl_shape_traits!(QuadraticBezier);
+
+impl QuadraticBezier {
+    fn mutate_once(&mut self, dimensions: (u32, u32), rate: f32, rng: &mut dyn RngCore) {
+        if self.alpha.mutate(4, rng) {
+            return;
+        }
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let dx = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        let dy = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        let mutate_point = |(x, y): (i32, i32)| (clamp(x + dx, 0, width-1), clamp(y + dy, 0, height-1));
+        match Uniform::new(0, 4).sample(rng) {
+            0 => self.start = mutate_point(self.start),
+            1 => self.control = mutate_point(self.control),
+            2 => self.end = mutate_point(self.end),
+            _ => {
+                // the stroke width is much more sensitive than a position
+                let delta = rng.sample::<f32, _>(StandardNormal).round() as i32;
+                self.width = clamp(self.width + delta, 1, width.min(height) / 2);
+            }
+        }
+        self.raster = Some(self.new_raster());
+    }
+
+    // Stamps a disc of the stroke's diameter about every pixel along the
+    // curve, then merges the overlapping spans.
+    fn new_raster(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let distance = |(x1, y1): (i32, i32), (x2, y2): (i32, i32)| {
+            (((x2 - x1).pow(2) + (y2 - y1).pow(2)) as f64).sqrt()
+        };
+        // the control polygon is never shorter than the curve
+        let length = distance(self.start, self.control) + distance(self.control, self.end);
+        let samples = length.ceil().max(1.0) as i32;
+        let radius = self.width.max(1) as f64 / 2.0;
+        for i in 0..samples+1 {
+            let (x_c, y_c) = self.point_at(i as f64 / samples as f64);
+            let (y1, y2) = ((y_c - radius).round() as i32, (y_c + radius).round() as i32);
+            for y in y1..y2+1 {
+                let dy = y as f64 - y_c;
+                let half = (radius * radius - dy * dy).max(0.0).sqrt();
+                rows.push(Row::new((x_c - half).round() as i32, (x_c + half).round() as i32, y));
+            }
+        }
+        Row::merge(rows)
+    }
+}
+
+// For printing QuadraticBezier information!
+impl fmt::Display for QuadraticBezier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Quadratic Bézier from ({}, {}) to ({}, {}) with control ({}, {}) and width {}",
+            self.start.0, self.start.1, self.end.0, self.end.1, self.control.0, self.control.1, self.width)
+    }
+}
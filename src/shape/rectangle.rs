@@ -0,0 +1,106 @@
+use std::fmt;
+
+use image::Rgb;
+
+use rand::{Rng, RngCore};
+use rand::distributions::Uniform;
+use rand_distr::{StandardNormal, Distribution};
+
+use crate::shape::{Shape, ShapeKind, Alpha};
+use crate::shape::{Row, scale_length};
+use crate::util::{clamp};
+use crate::svg;
+
+#[derive(Debug, Default, Clone)]
+pub struct Rectangle {
+    raster: Option<Vec<Row>>,
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+    alpha: Alpha,
+}
+
+impl Rectangle {
+    pub fn new(x1: i32, y1: i32, x2: i32, y2: i32, alpha: Alpha) -> Self {
+        let mut rectangle = Rectangle {raster: None, x1, y1, x2, y2, alpha};
+        rectangle.raster = Some(rectangle.new_raster());
+        rectangle
+    }
+    pub fn random<R: Rng + ?Sized>(dimensions: (u32, u32), alpha: Alpha, rng: &mut R) -> Self {
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let (x_distr, y_distr) = (Uniform::new(0, width), Uniform::new(0, height));
+        let x1 = x_distr.sample(rng);
+        let y1 = y_distr.sample(rng);
+        let x2 = x_distr.sample(rng);
+        let y2 = y_distr.sample(rng);
+        Rectangle::new(x1, y1, x2, y2, alpha)
+    }
+}
+
+impl Shape for Rectangle {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::Rectangle
+    }
+
+    fn scaled(&self, scale: f32) -> Box<dyn Shape> {
+        let (x1, x2) = (self.x1.min(self.x2), self.x1.max(self.x2));
+        let (y1, y2) = (self.y1.min(self.y2), self.y1.max(self.y2));
+        // the far edge of pixel x2 is at x2 + 1
+        Box::new(Rectangle::new(
+            scale_length(x1, scale), scale_length(y1, scale),
+            scale_length(x2 + 1, scale) - 1, scale_length(y2 + 1, scale) - 1,
+            self.alpha
+        ))
+    }
+
+    fn params(&self) -> Vec<i32> {
+        vec![self.x1, self.y1, self.x2, self.y2]
+    }
+
+    fn with_params(&self, params: &[i32]) -> Option<Box<dyn Shape>> {
+        match *params {
+            [x1, y1, x2, y2] => Some(Box::new(Rectangle::new(x1, y1, x2, y2, self.alpha))),
+            _ => None,
+        }
+    }
+
+    fn svg(&self, color: Rgb<u8>, alpha: u8) -> String {
+        let (x1, x2) = (self.x1.min(self.x2), self.x1.max(self.x2));
+        let (y1, y2) = (self.y1.min(self.y2), self.y1.max(self.y2));
+        format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {} />",
+            x1, y1, x2 - x1 + 1, y2 - y1 + 1, svg::fill(color, alpha))
+    }
+}
+
+impl_shape_traits!(Rectangle);
+
+impl Rectangle {
+    fn mutate_once(&mut self, dimensions: (u32, u32), rate: f32, rng: &mut dyn RngCore) {
+        if self.alpha.mutate(4, rng) {
+            return;
+        }
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let delta = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        match Uniform::new(0, 4).sample(rng) {
+            0 => self.x1 = clamp(self.x1 + delta, 0, width-1),
+            1 => self.y1 = clamp(self.y1 + delta, 0, height-1),
+            2 => self.x2 = clamp(self.x2 + delta, 0, width-1),
+            _ => self.y2 = clamp(self.y2 + delta, 0, height-1),
+        }
+        self.raster = Some(self.new_raster());
+    }
+
+    fn new_raster(&self) -> Vec<Row> {
+        let (x1, x2) = (self.x1.min(self.x2), self.x1.max(self.x2));
+        let (y1, y2) = (self.y1.min(self.y2), self.y1.max(self.y2));
+        (y1..y2+1).map(|y| Row::new(x1, x2, y)).collect()
+    }
+}
+
+// For printing Rectangle information!
+impl fmt::Display for Rectangle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Rectangle with corners ({}, {}) and ({}, {})", self.x1, self.y1, self.x2, self.y2)
+    }
+}
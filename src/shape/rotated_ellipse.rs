@@ -0,0 +1,127 @@
+use std::fmt;
+
+use image::Rgb;
+
+use rand::{Rng, RngCore};
+use rand::distributions::Uniform;
+use rand_distr::{StandardNormal, Distribution};
+
+use crate::shape::{Shape, ShapeKind, Alpha};
+use crate::shape::{Row, scale_index};
+use crate::util::{clamp};
+use crate::svg;
+
+#[derive(Debug, Default, Clone)]
+pub struct RotatedEllipse {
+    raster: Option<Vec<Row>>,
+    x: i32,
+    y: i32,
+    x_radius: i32,
+    y_radius: i32,
+    // rotation in degrees, clockwise in image coordinates
+    angle: i32,
+    alpha: Alpha,
+}
+
+impl RotatedEllipse {
+    pub fn new(x: i32, y: i32, x_radius: i32, y_radius: i32, angle: i32, alpha: Alpha) -> Self {
+        let mut ellipse = RotatedEllipse {raster: None, x, y, x_radius, y_radius, angle, alpha};
+        ellipse.raster = Some(ellipse.new_raster());
+        ellipse
+    }
+    pub fn random<R: Rng + ?Sized>(dimensions: (u32, u32), alpha: Alpha, rng: &mut R) -> Self {
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let (x_distr, y_distr) = (Uniform::new(0, width), Uniform::new(0, height));
+        let x = x_distr.sample(rng);
+        let y = y_distr.sample(rng);
+        let x_radius = Uniform::new_inclusive(1, (width - 1).max(1)).sample(rng);
+        let y_radius = Uniform::new_inclusive(1, (height - 1).max(1)).sample(rng);
+        let angle = Uniform::new(0, 180).sample(rng);
+        RotatedEllipse::new(x, y, x_radius, y_radius, angle, alpha)
+    }
+}
+
+impl Shape for RotatedEllipse {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::RotatedEllipse
+    }
+
+    fn scaled(&self, scale: f32) -> Box<dyn Shape> {
+        Box::new(RotatedEllipse::new(scale_index(self.x, scale), scale_index(self.y, scale), scale_index(self.x_radius, scale), scale_index(self.y_radius, scale), self.angle, self.alpha))
+    }
+
+    fn params(&self) -> Vec<i32> {
+        vec![self.x, self.y, self.x_radius, self.y_radius, self.angle]
+    }
+
+    fn with_params(&self, params: &[i32]) -> Option<Box<dyn Shape>> {
+        match *params {
+            [x, y, x_radius, y_radius, angle] => Some(Box::new(RotatedEllipse::new(x, y, x_radius, y_radius, angle, self.alpha))),
+            _ => None,
+        }
+    }
+
+    fn svg(&self, color: Rgb<u8>, alpha: u8) -> String {
+        format!("<ellipse transform=\"translate({} {}) rotate({})\" rx=\"{}\" ry=\"{}\" {} />",
+            self.x as f32 + 0.5, self.y as f32 + 0.5, self.angle, self.x_radius as f32 + 0.5, self.y_radius as f32 + 0.5, svg::fill(color, alpha))
+    }
+}
+
+impl_shape_traits!(RotatedEllipse);
+
+impl RotatedEllipse {
+    fn mutate_once(&mut self, dimensions: (u32, u32), rate: f32, rng: &mut dyn RngCore) {
+        if self.alpha.mutate(5, rng) {
+            return;
+        }
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let delta = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        match Uniform::new(0, 5).sample(rng) {
+            0 => self.x = clamp(self.x + delta, 0, width-1),
+            1 => self.y = clamp(self.y + delta, 0, height-1),
+            2 => self.x_radius = clamp(self.x_radius + delta, 1, (width-1).max(1)),
+            3 => self.y_radius = clamp(self.y_radius + delta, 1, (height-1).max(1)),
+            // angles need a larger step than pixel coordinates to move noticeably
+            _ => self.angle = (self.angle + 8 * delta).rem_euclid(180),
+        }
+        self.raster = Some(self.new_raster());
+    }
+
+    // For each row, solve the rotated ellipse equation as a quadratic in x:
+    //   A*x^2 + B*x + C <= 0
+    // where x, y are offsets from the center.
+    fn new_raster(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let (a, b) = (self.x_radius.max(1) as f64, self.y_radius.max(1) as f64);
+        let (x_c, y_c) = (self.x, self.y);
+        let theta = (self.angle as f64).to_radians();
+        let (sin, cos) = theta.sin_cos();
+        let (a2, b2) = (a * a, b * b);
+
+        let coef_a = cos * cos / a2 + sin * sin / b2;
+        let half_height = (a2 * sin * sin + b2 * cos * cos).sqrt().round() as i32;
+        for y in -half_height..half_height+1 {
+            let y_f = y as f64;
+            let coef_b = 2.0 * y_f * sin * cos * (1.0 / a2 - 1.0 / b2);
+            let coef_c = y_f * y_f * (sin * sin / a2 + cos * cos / b2) - 1.0;
+            let discriminant = coef_b * coef_b - 4.0 * coef_a * coef_c;
+            if discriminant < 0.0 {
+                continue;
+            }
+            let root = discriminant.sqrt();
+            let x1 = ((-coef_b - root) / (2.0 * coef_a)).round() as i32;
+            let x2 = ((-coef_b + root) / (2.0 * coef_a)).round() as i32;
+            if y_c + y >= 0 {
+                rows.push(Row::new(x_c + x1, x_c + x2, y_c + y));
+            }
+        }
+        rows
+    }
+}
+
+// For printing RotatedEllipse information!
+impl fmt::Display for RotatedEllipse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Rotated ellipse centered at ({}, {}) with radii ({}, {}) and angle {}", self.x, self.y, self.x_radius, self.y_radius, self.angle)
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+
+use image::Rgb;
+
+use rand::{Rng, RngCore};
+use rand::distributions::Uniform;
+use rand_distr::{StandardNormal, Distribution};
+
+use crate::shape::{Shape, ShapeKind, Alpha};
+use crate::shape::{Row, scale_length};
+use crate::util::{clamp};
+use crate::svg;
+
+#[derive(Debug, Default, Clone)]
+pub struct RotatedRectangle {
+    raster: Option<Vec<Row>>,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    // rotation in degrees, clockwise in image coordinates
+    angle: i32,
+    alpha: Alpha,
+}
+
+impl RotatedRectangle {
+    pub fn new(x: i32, y: i32, width: i32, height: i32, angle: i32, alpha: Alpha) -> Self {
+        let mut rectangle = RotatedRectangle {raster: None, x, y, width, height, angle, alpha};
+        rectangle.raster = Some(rectangle.new_raster());
+        rectangle
+    }
+    pub fn random<R: Rng + ?Sized>(dimensions: (u32, u32), alpha: Alpha, rng: &mut R) -> Self {
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let (x_distr, y_distr) = (Uniform::new(0, width), Uniform::new(0, height));
+        let x = x_distr.sample(rng);
+        let y = y_distr.sample(rng);
+        let rect_width = Uniform::new_inclusive(1, (width - 1).max(1)).sample(rng);
+        let rect_height = Uniform::new_inclusive(1, (height - 1).max(1)).sample(rng);
+        let angle = Uniform::new(0, 180).sample(rng);
+        RotatedRectangle::new(x, y, rect_width, rect_height, angle, alpha)
+    }
+
+    fn corners(&self) -> Vec<(f64, f64)> {
+        let (sin, cos) = (self.angle as f64).to_radians().sin_cos();
+        let (half_w, half_h) = (self.width as f64 / 2.0, self.height as f64 / 2.0);
+        let (x_c, y_c) = (self.x as f64, self.y as f64);
+        [(-half_w, -half_h), (half_w, -half_h), (half_w, half_h), (-half_w, half_h)]
+            .iter()
+            .map(|&(dx, dy)| (x_c + dx * cos - dy * sin, y_c + dx * sin + dy * cos))
+            .collect()
+    }
+}
+
+impl Shape for RotatedRectangle {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::RotatedRectangle
+    }
+
+    fn scaled(&self, scale: f32) -> Box<dyn Shape> {
+        Box::new(RotatedRectangle::new(scale_length(self.x, scale), scale_length(self.y, scale), scale_length(self.width, scale), scale_length(self.height, scale), self.angle, self.alpha))
+    }
+
+    fn params(&self) -> Vec<i32> {
+        vec![self.x, self.y, self.width, self.height, self.angle]
+    }
+
+    fn with_params(&self, params: &[i32]) -> Option<Box<dyn Shape>> {
+        match *params {
+            [x, y, width, height, angle] => Some(Box::new(RotatedRectangle::new(x, y, width, height, angle, self.alpha))),
+            _ => None,
+        }
+    }
+
+    fn svg(&self, color: Rgb<u8>, alpha: u8) -> String {
+        // the corners are already in continuous coordinates, see Row::polygon
+        format!("<rect transform=\"translate({} {}) rotate({})\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {} />",
+            self.x, self.y, self.angle, -self.width as f32 / 2.0, -self.height as f32 / 2.0, self.width, self.height, svg::fill(color, alpha))
+    }
+}
+
+impl_shape_traits!(RotatedRectangle);
+
+impl RotatedRectangle {
+    fn mutate_once(&mut self, dimensions: (u32, u32), rate: f32, rng: &mut dyn RngCore) {
+        if self.alpha.mutate(5, rng) {
+            return;
+        }
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let delta = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        match Uniform::new(0, 5).sample(rng) {
+            0 => self.x = clamp(self.x + delta, 0, width-1),
+            1 => self.y = clamp(self.y + delta, 0, height-1),
+            2 => self.width = clamp(self.width + delta, 1, (width-1).max(1)),
+            3 => self.height = clamp(self.height + delta, 1, (height-1).max(1)),
+            // angles need a larger step than pixel coordinates to move noticeably
+            _ => self.angle = (self.angle + 8 * delta).rem_euclid(180),
+        }
+        self.raster = Some(self.new_raster());
+    }
+
+    fn new_raster(&self) -> Vec<Row> {
+        Row::polygon(&self.corners())
+    }
+}
+
+// For printing RotatedRectangle information!
+impl fmt::Display for RotatedRectangle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Rotated rectangle centered at ({}, {}) with size ({}, {}) and angle {}", self.x, self.y, self.width, self.height, self.angle)
+    }
+}
@@ -22,6 +22,65 @@ impl Row {
         }
         vec
     }
+
+    // The part of the row inside a `width` by `height` canvas as (x1, x2, y),
+    // or None if it lies entirely outside
+    pub fn clip(&self, width: u32, height: u32) -> Option<(u32, u32, u32)> {
+        let (width, height) = (width as i32, height as i32);
+        let (x1, x2) = (self.x1.max(0), self.x2.min(width - 1));
+        if self.y < 0 || self.y >= height || x1 > x2 {
+            return None;
+        }
+        Some((x1 as u32, x2 as u32, self.y as u32))
+    }
+
+    // Even-odd scanline fill of a closed polygon, sampling pixel centers.
+    // Each scanline may produce several disjoint rows.
+    pub fn polygon(points: &[(f64, f64)]) -> Vec<Self> {
+        let mut vec = Vec::new();
+        if points.len() < 3 {
+            return vec;
+        }
+        let y_min = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        let y_max = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+        let mut crossings: Vec<f64> = Vec::new();
+        for y in (y_min.floor() as i32)..(y_max.ceil() as i32 + 1) {
+            let scan_y = y as f64 + 0.5;
+            crossings.clear();
+            for i in 0..points.len() {
+                let (x1, y1) = points[i];
+                let (x2, y2) = points[(i + 1) % points.len()];
+                if (y1 <= scan_y && scan_y < y2) || (y2 <= scan_y && scan_y < y1) {
+                    crossings.push(x1 + (scan_y - y1) / (y2 - y1) * (x2 - x1));
+                }
+            }
+            crossings.sort_by(|a, b| a.partial_cmp(b).expect("NaN polygon crossing"));
+            for pair in crossings.chunks_exact(2) {
+                let x1 = (pair[0] - 0.5).ceil() as i32;
+                let x2 = (pair[1] - 0.5).floor() as i32;
+                if x1 <= x2 {
+                    vec.push(Self::new(x1, x2, y));
+                }
+            }
+        }
+        vec
+    }
+
+    // Merges overlapping or touching rows on the same line, so that no pixel
+    // is covered (and blended) more than once.
+    pub fn merge(mut rows: Vec<Self>) -> Vec<Self> {
+        rows.sort_by_key(|row| (row.y, row.x1));
+        let mut vec: Vec<Self> = Vec::with_capacity(rows.len());
+        for row in rows {
+            match vec.last_mut() {
+                Some(last) if last.y == row.y && row.x1 <= last.x2 + 1 => {
+                    last.x2 = last.x2.max(row.x2);
+                }
+                _ => vec.push(row),
+            }
+        }
+        vec
+    }
 }
 
 impl From<Row> for (i32, i32, i32) {
@@ -52,4 +111,32 @@ impl PartialEq for Row {
     fn eq(&self, other: &Self) -> bool {
         self.y == other.y
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(rows: &[(i32, i32, i32)]) -> Vec<(i32, i32, i32)> {
+        let rows = rows.iter().map(|&(x1, x2, y)| Row::new(x1, x2, y)).collect();
+        Row::merge(rows).into_iter().map(|row| row.into()).collect()
+    }
+
+    #[test]
+    fn merge_joins_overlapping_and_touching_rows() {
+        assert_eq!(merged(&[(0, 5, 0), (3, 8, 0)]), [(0, 8, 0)]);
+        assert_eq!(merged(&[(0, 5, 0), (6, 8, 0)]), [(0, 8, 0)]);
+        assert_eq!(merged(&[(2, 3, 0), (0, 9, 0)]), [(0, 9, 0)]);
+    }
+
+    #[test]
+    fn merge_keeps_separate_rows_apart() {
+        assert_eq!(merged(&[(0, 5, 0), (7, 8, 0)]), [(0, 5, 0), (7, 8, 0)]);
+        assert_eq!(merged(&[(0, 5, 1), (0, 5, 0)]), [(0, 5, 0), (0, 5, 1)]);
+    }
+
+    #[test]
+    fn merge_sorts_unordered_rows() {
+        let rows = [(7, 9, 1), (4, 6, 0), (0, 2, 1), (0, 3, 0), (3, 5, 1)];
+        assert_eq!(merged(&rows), [(0, 6, 0), (0, 5, 1), (7, 9, 1)]);
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+use image::Rgb;
+
+use rand::{Rng, RngCore};
+use rand::distributions::Uniform;
+use rand_distr::{StandardNormal, Distribution};
+
+use crate::shape::{Shape, ShapeKind, Alpha};
+use crate::shape::{Row, scale_index};
+use crate::util::{clamp};
+use crate::svg;
+
+#[derive(Debug, Default, Clone)]
+pub struct Triangle {
+    raster: Option<Vec<Row>>,
+    vertices: [(i32, i32); 3],
+    alpha: Alpha,
+}
+
+impl Triangle {
+    pub fn new(vertices: [(i32, i32); 3], alpha: Alpha) -> Self {
+        let mut triangle = Triangle {raster: None, vertices, alpha};
+        triangle.raster = Some(triangle.new_raster());
+        triangle
+    }
+    pub fn random<R: Rng + ?Sized>(dimensions: (u32, u32), alpha: Alpha, rng: &mut R) -> Self {
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let (x_distr, y_distr) = (Uniform::new(0, width), Uniform::new(0, height));
+        let mut vertices = [(0, 0); 3];
+        for vertex in vertices.iter_mut() {
+            *vertex = (x_distr.sample(rng), y_distr.sample(rng));
+        }
+        Triangle::new(vertices, alpha)
+    }
+}
+
+impl Shape for Triangle {
+    fn kind(&self) -> ShapeKind {
+        ShapeKind::Triangle
+    }
+
+    fn scaled(&self, scale: f32) -> Box<dyn Shape> {
+        let mut vertices = self.vertices;
+        for vertex in vertices.iter_mut() {
+            *vertex = (scale_index(vertex.0, scale), scale_index(vertex.1, scale));
+        }
+        Box::new(Triangle::new(vertices, self.alpha))
+    }
+
+    fn params(&self) -> Vec<i32> {
+        self.vertices.iter().flat_map(|&(x, y)| [x, y]).collect()
+    }
+
+    fn with_params(&self, params: &[i32]) -> Option<Box<dyn Shape>> {
+        match *params {
+            [x1, y1, x2, y2, x3, y3] => Some(Box::new(Triangle::new([(x1, y1), (x2, y2), (x3, y3)], self.alpha))),
+            _ => None,
+        }
+    }
+
+    fn svg(&self, color: Rgb<u8>, alpha: u8) -> String {
+        let points: Vec<String> = self.vertices.iter()
+            .map(|&(x, y)| format!("{},{}", x as f32 + 0.5, y as f32 + 0.5))
+            .collect();
+        format!("<polygon points=\"{}\" {} />", points.join(" "), svg::fill(color, alpha))
+    }
+}
+
+impl_shape_traits!(Triangle);
+
+impl Triangle {
+    fn mutate_once(&mut self, dimensions: (u32, u32), rate: f32, rng: &mut dyn RngCore) {
+        if self.alpha.mutate(3, rng) {
+            return;
+        }
+        let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+        let i = Uniform::new(0, 3).sample(rng);
+        let (x, y) = self.vertices[i];
+        let dx = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        let dy = (rate * rng.sample::<f32, _>(StandardNormal)).round() as i32;
+        self.vertices[i] = (clamp(x + dx, 0, width-1), clamp(y + dy, 0, height-1));
+        self.raster = Some(self.new_raster());
+    }
+
+    // Edge walking: with the vertices sorted top to bottom, every row spans
+    // from the long edge (top to bottom vertex) to one of the two short edges.
+    // A flat bottom's last row ends at the middle vertex, which the lower
+    // short edge would collapse onto the bottom vertex.
+    fn new_raster(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let mut vertices = self.vertices;
+        vertices.sort_by_key(|&(_x, y)| y);
+        let [top, middle, bottom] = vertices;
+        if top.1 == bottom.1 {
+            let x1 = top.0.min(middle.0).min(bottom.0);
+            let x2 = top.0.max(middle.0).max(bottom.0);
+            rows.push(Row::new(x1, x2, top.1));
+            return rows;
+        }
+        for y in top.1..bottom.1+1 {
+            let long_x = edge_x(top, bottom, y);
+            let short_x = if y < middle.1 || middle.1 == bottom.1 {
+                edge_x(top, middle, y)
+            } else {
+                edge_x(middle, bottom, y)
+            };
+            rows.push(Row::new(long_x.min(short_x), long_x.max(short_x), y));
+        }
+        rows
+    }
+}
+
+// x coordinate of the edge from `start` to `end` at row `y`
+fn edge_x(start: (i32, i32), end: (i32, i32), y: i32) -> i32 {
+    let (x1, y1) = start;
+    let (x2, y2) = end;
+    if y1 == y2 {
+        return x2;
+    }
+    let t = (y - y1) as f64 / (y2 - y1) as f64;
+    (x1 as f64 + t * (x2 - x1) as f64).round() as i32
+}
+
+// For printing Triangle information!
+impl fmt::Display for Triangle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [(x1, y1), (x2, y2), (x3, y3)] = self.vertices;
+        write!(f, "Triangle with vertices ({}, {}), ({}, {}), ({}, {})", x1, y1, x2, y2, x3, y3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Rasterizable;
+
+    fn rows(vertices: [(i32, i32); 3]) -> Vec<(i32, i32, i32)> {
+        Triangle::new(vertices, Alpha::default()).rasterize().iter().map(|&row| row.into()).collect()
+    }
+
+    #[test]
+    fn flat_bottom_spans_the_whole_last_row() {
+        assert_eq!(rows([(5, 0), (0, 5), (10, 5)]).last(), Some(&(0, 10, 5)));
+        assert_eq!(rows([(0, 0), (10, 5), (0, 5)]).last(), Some(&(0, 10, 5)));
+    }
+
+    #[test]
+    fn flat_top_spans_the_whole_first_row() {
+        assert_eq!(rows([(0, 0), (10, 0), (5, 5)]).first(), Some(&(0, 10, 0)));
+        assert_eq!(rows([(10, 0), (0, 0), (5, 5)]).first(), Some(&(0, 10, 0)));
+    }
+
+    #[test]
+    fn rows_cover_every_line_once() {
+        let rows = rows([(2, 1), (9, 4), (0, 8)]);
+        let lines: Vec<i32> = rows.iter().map(|&(_, _, y)| y).collect();
+        assert_eq!(lines, (1..=8).collect::<Vec<_>>());
+        assert!(rows.iter().all(|&(x1, x2, _)| x1 <= x2));
+    }
+}
@@ -0,0 +1,41 @@
+use image::Rgb;
+
+// Wraps the shape elements in `body` in an svg document displayed at
+// `output_size`, on top of a rectangle filled with the background color.
+// The shapes' own coordinates span `size`.
+pub fn document(size: (u32, u32), output_size: (u32, u32), background: Rgb<u8>, body: &str) -> String {
+    let (width, height) = size;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+        output_size.0, output_size.1, width, height
+    );
+    svg += &format!(
+        "<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"{}\" />\n",
+        width, height, hex(background)
+    );
+    svg += body;
+    svg += "</svg>\n";
+    svg
+}
+
+pub fn hex(color: Rgb<u8>) -> String {
+    let [r, g, b] = color.0;
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+pub fn opacity(alpha: u8) -> String {
+    format!("{:.3}", alpha as f32 / 255.0)
+}
+
+// attributes for a filled shape
+pub fn fill(color: Rgb<u8>, alpha: u8) -> String {
+    format!("fill=\"{}\" fill-opacity=\"{}\"", hex(color), opacity(alpha))
+}
+
+// attributes for a stroked, unfilled shape
+pub fn stroke(color: Rgb<u8>, alpha: u8, width: i32) -> String {
+    format!(
+        "fill=\"none\" stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\" stroke-linecap=\"round\"",
+        hex(color), opacity(alpha), width
+    )
+}
@@ -2,8 +2,11 @@
 
 use image::RgbImage;
 use image::Rgb;
+use image::imageops::{self, FilterType};
 
 use crate::shape::{Row, Shape};
+use crate::metric::{self, Metric};
+use crate::weights::{Weights, weight};
 
 pub fn average_image_color(img: &RgbImage) -> Rgb<u8> {
     let mut sum_r: u32 = 0;
@@ -26,20 +29,29 @@ pub fn average_image_color(img: &RgbImage) -> Rgb<u8> {
     Rgb([avg_r, avg_g, avg_b])
 }
 
-pub fn average_color_in_lines(rows: &Vec<Row>, img: &RgbImage) -> Rgb<u8> {
+// Shrinks `img` with bilinear filtering so that neither side exceeds
+// `max_size`, keeping its aspect ratio.  Smaller images are returned as is.
+pub fn resize_to_fit(img: &RgbImage, max_size: u32) -> RgbImage {
+    let (width, height) = img.dimensions();
+    if width.max(height) <= max_size {
+        return img.clone();
+    }
+    let scale = max_size as f32 / width.max(height) as f32;
+    let new_width = ((width as f32 * scale).round() as u32).max(1);
+    let new_height = ((height as f32 * scale).round() as u32).max(1);
+    imageops::resize(img, new_width, new_height, FilterType::Triangle)
+}
+
+pub fn average_color_in_lines(rows: &[Row], img: &RgbImage) -> Rgb<u8> {
     let mut sum_r: u32 = 0;
     let mut sum_g: u32 = 0;
     let mut sum_b: u32 = 0;
     let mut count: u32 = 0;
     let (width, height) = img.dimensions();
-    let (width, height) = (width as i32, height as i32);
-    for &row in rows {   
-        let (x1, x2, y) = row.into();
-        if y < 0 || y >= height {
+    for row in rows {
+        let Some((x1, x2, y)) = row.clip(width, height) else {
             continue;
-        }
-        let (x1, x2) = (clamp(x1, 0, width-1) as u32, clamp(x2, 0, width-1) as u32);
-        let y = y as u32;
+        };
         for x in x1..x2+1 {
             let pixel = img.get_pixel(x, y);
             let [r, g, b] = pixel.0;
@@ -49,27 +61,45 @@ pub fn average_color_in_lines(rows: &Vec<Row>, img: &RgbImage) -> Rgb<u8> {
             count += 1;
         }
     }
+    if count == 0 {
+        return Rgb([0, 0, 0]);
+    }
     let avg_r = (sum_r as f32 / count as f32).round() as u8;
     let avg_g = (sum_g as f32 / count as f32).round() as u8;
     let avg_b = (sum_b as f32 / count as f32).round() as u8;
     Rgb([avg_r, avg_g, avg_b])
 }
 
-pub fn best_color_in_rows(rows: &Vec<Row>, alpha: u8, source: &RgbImage, target: &RgbImage) -> Rgb<u8> {
+// The color that, drawn over `rows` with opacity `alpha`, brings `source`
+// closest to `target` under `metric`.  For rgb this is exact: the mean of the
+// colors that would cover each pixel perfectly.  The CIELAB metrics take the
+// mean of those colors in CIELAB instead, which is exact for CIE76 on opaque
+// shapes and close otherwise.  Rows covering only pixels of no weight get the
+// unweighted best color, and rows covering no pixel at all get black, which
+// draws nothing.
+pub fn best_color_in_rows(rows: &[Row], alpha: u8, source: &RgbImage, target: &RgbImage, metric: Metric, weights: Option<&Weights>) -> Rgb<u8> {
+    let best_color = |weights| if metric.is_lab() {
+        best_lab_color_in_rows(rows, alpha, source, target, weights)
+    } else {
+        best_rgb_color_in_rows(rows, alpha, source, target, weights)
+    };
+    best_color(weights)
+        .or_else(|| weights.and_then(|_| best_color(None)))
+        .unwrap_or(Rgb([0, 0, 0]))
+}
+
+// None when the rows cover no weight
+fn best_rgb_color_in_rows(rows: &[Row], alpha: u8, source: &RgbImage, target: &RgbImage, weights: Option<&Weights>) -> Option<Rgb<u8>> {
     let alpha: f32 = alpha as f32 / 255.0;
     let mut sum_r: f32 = 0.0;
     let mut sum_g: f32 = 0.0;
     let mut sum_b: f32 = 0.0;
-    let mut count: u32 = 0;
+    let mut count: f32 = 0.0;
     let (width, height) = source.dimensions();
-    let (width, height) = (width as i32, height as i32);
-    for &row in rows {   
-        let (x1, x2, y) = row.into();
-        if y < 0 || y >= height {
+    for row in rows {
+        let Some((x1, x2, y)) = row.clip(width, height) else {
             continue;
-        }
-        let (x1, x2) = (clamp(x1, 0, width-1) as u32, clamp(x2, 0, width-1) as u32);
-        let y = y as u32;
+        };
         for x in x1..x2+1 {
             let src_pixel = source.get_pixel(x, y);
             let [src_r, src_g, src_b] = src_pixel.0;
@@ -101,81 +131,149 @@ pub fn best_color_in_rows(rows: &Vec<Row>, alpha: u8, source: &RgbImage, target:
                 ];
 
 
-            sum_r += cover_r;
-            sum_g += cover_g;
-            sum_b += cover_b;
-            count += 1;
+            let weight = weight(weights, x, y);
+            sum_r += weight * cover_r;
+            sum_g += weight * cover_g;
+            sum_b += weight * cover_b;
+            count += weight;
         }
     }
-    let avg_r = (255.0 * sum_r / count as f32).round() as u8;
-    let avg_g = (255.0 * sum_g / count as f32).round() as u8;
-    let avg_b = (255.0 * sum_b / count as f32).round() as u8;
-    Rgb([avg_r, avg_g, avg_b])
+    if count <= 0.0 {
+        return None;
+    }
+    let avg_r = (255.0 * sum_r / count).round() as u8;
+    let avg_g = (255.0 * sum_g / count).round() as u8;
+    let avg_b = (255.0 * sum_b / count).round() as u8;
+    Some(Rgb([avg_r, avg_g, avg_b]))
 }
 
-pub fn best_color_in_shape(shape: &Box<dyn Shape>, alpha: u8, source: &RgbImage, target: &RgbImage) -> Rgb<u8> {
-    let rows = shape.rasterize();
-    best_color_in_rows(&rows, alpha, source, target)
+fn best_lab_color_in_rows(rows: &[Row], alpha: u8, source: &RgbImage, target: &RgbImage, weights: Option<&Weights>) -> Option<Rgb<u8>> {
+    let alpha: f64 = alpha as f64 / 255.0;
+    let mut sum = [0.0; 3];
+    let mut count: f64 = 0.0;
+    let (width, height) = source.dimensions();
+    for row in rows {
+        let Some((x1, x2, y)) = row.clip(width, height) else {
+            continue;
+        };
+        for x in x1..x2+1 {
+            let src_pixel = source.get_pixel(x, y).0;
+            let target_pixel = target.get_pixel(x, y).0;
+            // colors outside the gamut have no meaningful CIELAB coordinates
+            let cover = |i: usize| {
+                let (src, target) = (src_pixel[i] as f64 / 255.0, target_pixel[i] as f64 / 255.0);
+                ((target + src * (alpha - 1.0)) / alpha).clamp(0.0, 1.0)
+            };
+            let lab = metric::lab_from_unit([cover(0), cover(1), cover(2)]);
+            let weight = weight(weights, x, y) as f64;
+            for (sum, value) in sum.iter_mut().zip(lab.iter()) {
+                *sum += weight * value;
+            }
+            count += weight;
+        }
+    }
+    if count <= 0.0 {
+        return None;
+    }
+    let [l, a, b] = sum;
+    Some(metric::rgb_from_lab([l / count, a / count, b / count]))
 }
 
-pub fn mean_square_error(img1: &RgbImage, img2: &RgbImage) -> f32 {
-    let mut error: f32 = 0.0;
-    assert_eq!(img1.dimensions(), img2.dimensions());
-    let count: u32 = img1.dimensions().0 * img1.dimensions().1;
-
-    let mut img1_pixels = img1.pixels();
-    let mut img2_pixels = img2.pixels();
-    for _ in 0..count {
-        let [r1, g1, b1] = (*img1_pixels.next().unwrap()).0;
-        let [r2, g2, b2] = (*img2_pixels.next().unwrap()).0;
-        let [dr, dg, db] = [
-            ((r1 as i32) - (r2 as i32)).pow(2) as f32,
-            ((g1 as i32) - (g2 as i32)).pow(2) as f32,
-            ((b1 as i32) - (b2 as i32)).pow(2) as f32
-        ];
-        error += dr + dg + db;
+pub fn best_color_in_shape(shape: &dyn Shape, alpha: u8, source: &RgbImage, target: &RgbImage, metric: Metric, weights: Option<&Weights>) -> Rgb<u8> {
+    best_color_in_rows(shape.rasterize(), alpha, source, target, metric, weights)
+}
+
+// Root mean square rgb difference, the same as `Metric::Rgb.error`
+pub fn mean_square_error(img1: &RgbImage, img2: &RgbImage, weights: Option<&Weights>) -> f32 {
+    Metric::Rgb.error(img1, img2, weights)
+}
+
+// Updates the `error` between `source` and `target` under `metric` for `color`
+// being drawn over `rows`, visiting only the covered pixels.  Sums are kept in
+// f64 since they span the whole image.
+#[allow(clippy::too_many_arguments)]
+pub fn partial_square_error(error: f32, rows: &[Row], color: Rgb<u8>, alpha: u8, source: &RgbImage, target: &RgbImage, metric: Metric, weights: Option<&Weights>) -> f32 {
+    assert_eq!(source.dimensions(), target.dimensions());
+    let (width, height) = source.dimensions();
+    let count = (width * height) as f64;
+    let mut squared_error: f64 = (error as f64).powi(2) * count;
+
+    for row in rows {
+        let Some((x1, x2, y)) = row.clip(width, height) else {
+            continue;
+        };
+        for x in x1..x2+1 {
+            let target_pixel = *target.get_pixel(x, y);
+            let before = *source.get_pixel(x, y);
+            let after = blend(before, color, alpha);
+            let weight = weight(weights, x, y) as f64;
+            squared_error -= weight * metric.difference(before, target_pixel);
+            squared_error += weight * metric.difference(after, target_pixel);
+        }
     }
-    error = (error/ ((3 * count) as f32)).sqrt();
-    // dbg!(error);
-    return error;
+    (squared_error.max(0.0) / count).sqrt() as f32
 }
 
-pub fn partial_square_error(error: f32, before: &RgbImage, after: &RgbImage, target: &RgbImage) -> f32 {
-    assert_eq!(before.dimensions(), after.dimensions());
-    assert_eq!(after.dimensions(), target.dimensions());
-    let count: u32 = target.dimensions().0 * target.dimensions().1;
-    let mut squared_error: f32 = error.powf(2.0) * (3 * count) as f32;
-
-    let mut before_pixels = before.pixels();
-    let mut after_pixels = after.pixels();
-    let mut target_pixels = target.pixels();
-    for _ in 0..count {
-        let [target_r, target_g, target_b] = (*target_pixels.next().unwrap()).0;
-        let [before_r, before_g, before_b] = (*before_pixels.next().unwrap()).0;
-        let [after_r, after_g, after_b] = (*after_pixels.next().unwrap()).0;
-        let [dr, dg, db] = [
-            ((target_r as i32) - (before_r as i32)).pow(2) as f32,
-            ((target_g as i32) - (before_g as i32)).pow(2) as f32,
-            ((target_b as i32) - (before_b as i32)).pow(2) as f32
-        ];
-        squared_error -= dr + dg + db;
-        let [dr, dg, db] = [
-            ((target_r as i32) - (after_r as i32)).pow(2) as f32,
-            ((target_g as i32) - (after_g as i32)).pow(2) as f32,
-            ((target_b as i32) - (after_b as i32)).pow(2) as f32
+// `color` drawn over `pixel` with opacity `alpha`
+pub fn blend(pixel: Rgb<u8>, color: Rgb<u8>, alpha: u8) -> Rgb<u8> {
+    let alpha: f32 = alpha as f32 / 255.0;
+    let [img_r, img_g, img_b] = pixel.0;
+    let [r, g, b] = color.0;
+
+    let [new_r, new_g, new_b] = [
+        (r as f32 * alpha + img_r as f32 * (1.0  - alpha)).round() as i32,
+        (g as f32 * alpha + img_g as f32 * (1.0 - alpha)).round() as i32,
+        (b as f32 * alpha + img_b as f32 * (1.0 - alpha)).round() as i32
         ];
-        squared_error += dr + dg + db;
-    }
-    squared_error = (squared_error/ ((3 * count) as f32)).sqrt();
-    squared_error
+    Rgb([
+        clamp(new_r, 0, 255) as u8,
+        clamp(new_g, 0, 255) as u8,
+        clamp(new_b, 0, 255) as u8
+        ])
 }
 
 pub fn clamp(input: i32, min: i32, max: i32) -> i32 {
     if input < min {
-        return min;
+        min
     } else if input > max {
-        return max;
+        max
     } else {
-        return input;
+        input
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32, seed: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            let value = |k: u32| ((x * 37 + y * 91 + seed * 53 + k * 17) % 256) as u8;
+            Rgb([value(0), value(1), value(2)])
+        })
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn partial_square_error_matches_full_error() {
+        let (source, target) = (image(12, 9, 1), image(12, 9, 2));
+        // the rows overhang the canvas, which must be clipped
+        let rows = [Row::new(-3, 4, 0), Row::new(2, 20, 4), Row::new(0, 11, 8), Row::new(0, 5, 9)];
+        let (color, alpha) = (Rgb([200, 40, 90]), 160);
+        let weights = Weights::from_fn(12, 9, |x, y| 1.0 + ((x + y) % 3) as f32).unwrap();
+        let mut drawn = source.clone();
+        for row in &rows {
+            if let Some((x1, x2, y)) = row.clip(12, 9) {
+                for x in x1..x2+1 {
+                    drawn.put_pixel(x, y, blend(*source.get_pixel(x, y), color, alpha));
+                }
+            }
+        }
+        for &metric in &[Metric::Rgb, Metric::Cie76, Metric::Ciede2000] {
+            for &weights in &[None, Some(&weights)] {
+                let before = metric.error(&source, &target, weights);
+                let partial = partial_square_error(before, &rows, color, alpha, &source, &target, metric, weights);
+                let full = metric.error(&drawn, &target, weights);
+                assert!((partial - full).abs() < 1e-3, "{:?}: {} != {}", metric, partial, full);
+            }
+        }
+    }
+}
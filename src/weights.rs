@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use image::{GrayImage, RgbImage, Luma};
+use image::imageops::{self, FilterType};
+
+use crate::error;
+
+// How much each pixel's error counts, so the optimizer spends more shapes on
+// bright areas of the map.  Weights are scaled to a mean of 1, which keeps
+// weighted errors comparable with unweighted ones.
+#[derive(Debug, Clone)]
+pub struct Weights {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+}
+
+impl Weights {
+    // A map from a grayscale image, stretched to `size` if needed
+    pub fn from_image(img: &GrayImage, (width, height): (u32, u32)) -> error::Result<Self> {
+        let img = if img.dimensions() == (width, height) {
+            img.clone()
+        } else {
+            imageops::resize(img, width, height, FilterType::Triangle)
+        };
+        let values = img.pixels().map(|pixel| pixel.0[0] as f32).collect();
+        Weights::from_values(width, height, values)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P, size: (u32, u32)) -> error::Result<Self> {
+        let path = path.as_ref();
+        let img = image::open(path).map_err(error::at_path(path))?.into_luma8();
+        Weights::from_image(&img, size)
+    }
+
+    pub fn from_fn<F: Fn(u32, u32) -> f32>(width: u32, height: u32, weight: F) -> error::Result<Self> {
+        let values = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| weight(x, y)).collect();
+        Weights::from_values(width, height, values)
+    }
+
+    // Weights detailed areas of `target` more, using the blurred brightness
+    // gradient.  Flat areas keep a quarter of the weight so they still get
+    // their colors right.
+    pub fn detail(target: &RgbImage) -> error::Result<Self> {
+        let (width, height) = target.dimensions();
+        let gray = imageops::grayscale(target);
+        let brightness = |x: i64, y: i64| {
+            let (x, y) = (x.clamp(0, width as i64 - 1) as u32, y.clamp(0, height as i64 - 1) as u32);
+            gray.get_pixel(x, y).0[0] as f32
+        };
+        let gradient = GrayImage::from_fn(width, height, |x, y| {
+            let (x, y) = (x as i64, y as i64);
+            let dx = brightness(x + 1, y) - brightness(x - 1, y);
+            let dy = brightness(x, y + 1) - brightness(x, y - 1);
+            Luma([(dx.hypot(dy) / 2.0).min(255.0) as u8])
+        });
+        let sigma = width.max(height) as f32 / 50.0;
+        let gradient = imageops::blur(&gradient, sigma.max(1.0));
+        let max = gradient.pixels().map(|pixel| pixel.0[0]).max().unwrap_or(0).max(1) as f32;
+        let values = gradient.pixels().map(|pixel| 0.25 + 0.75 * pixel.0[0] as f32 / max).collect();
+        Weights::from_values(width, height, values)
+    }
+
+    fn from_values(width: u32, height: u32, mut values: Vec<f32>) -> error::Result<Self> {
+        if values.iter().any(|value| !value.is_finite() || *value < 0.0) {
+            return Err(error::Error::ArgumentError("weights must be finite and not negative".to_string()));
+        }
+        let mean = values.iter().map(|&value| value as f64).sum::<f64>() / values.len().max(1) as f64;
+        if mean <= 0.0 {
+            return Err(error::Error::ArgumentError("the weight map is entirely black".to_string()));
+        }
+        for value in values.iter_mut() {
+            *value = (*value as f64 / mean) as f32;
+        }
+        Ok(Weights { width, height, values })
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> f32 {
+        self.values[(y * self.width + x) as usize]
+    }
+
+    // The weights as a grayscale image, brightest where the weight is highest
+    pub fn to_image(&self) -> GrayImage {
+        let max = self.values.iter().cloned().fold(0.0, f32::max).max(f32::MIN_POSITIVE);
+        GrayImage::from_fn(self.width, self.height, |x, y| {
+            Luma([(255.0 * self.get(x, y) / max).round() as u8])
+        })
+    }
+}
+
+// The weight of a pixel, 1 when there's no map
+pub fn weight(weights: Option<&Weights>, x: u32, y: u32) -> f32 {
+    weights.map_or(1.0, |weights| weights.get(x, y))
+}